@@ -1,10 +1,42 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contracterror, contractimpl, contracttype, symbol_short, Address, Bytes, Env,
-    Val, Vec, IntoVal,
+    contract, contracterror, contractimpl, contracttype, symbol_short,
+    xdr::{FromXdr, ToXdr},
+    Address, Bytes, BytesN, Env, IntoVal, Symbol, TryFromVal, Val, Vec,
 };
 
+// Số ledger trong một tuần (ledger ~5 giây), dùng để chia cohort fan
+const LEDGERS_PER_WEEK: u32 = 120_960;
+const SECONDS_PER_LEDGER: u64 = 5;
+
+// Số mục tối đa giữ lại trong nhật ký admin (ring buffer)
+const ADMIN_LOG_CAP: u32 = 200;
+// Số kỳ sao kê giữ lại cho mỗi creator, kỳ cũ hơn có thể bị xoá
+const RETAINED_STATEMENT_PERIODS: u64 = 12;
+// Số bản URI theo ngôn ngữ tối đa cho một token
+const MAX_URI_VARIANTS: u32 = 8;
+// Giới hạn liên kết ngoài (mạng xã hội, website) cho một token
+const MAX_EXTERNAL_LINKS: u32 = 5;
+const MAX_LINK_URL_LEN: u32 = 256;
+// Số đợt khuyến mãi chưa kết thúc tối đa của một creator
+const MAX_PROMOS_PER_CREATOR: u32 = 5;
+// Độ dài tối đa lời nhắn kèm quà tặng
+const MAX_GIFT_MEMO_LEN: u32 = 280;
+// Số người đồng sở hữu tối đa của một token
+const MAX_SHARE_HOLDERS: u32 = 10;
+// Số token tối đa mỗi lần dump_tokens, để kết quả nằm trong giới hạn của host
+const MAX_DUMP_TOKENS: u32 = 50;
+// Số fan trên bảng xếp hạng đốt điểm
+const BURN_LEADERBOARD_SIZE: u32 = 10;
+// Số thành viên tối đa của hội đồng multi-sig
+const MAX_COUNCIL_SIGNERS: u32 = 10;
+// Số fan mỗi trang của một cohort
+const COHORT_PAGE_SIZE: u32 = 100;
+// Ngưỡng dust tối đa (đơn vị nhỏ nhất của payment token); ngưỡng lớn hơn sẽ
+// gộp cả royalty thật vào phần của seller
+const MAX_DUST_THRESHOLD: i128 = 10_000;
+
 // ===========================
 // Storage keys
 // ===========================
@@ -19,14 +51,178 @@ pub enum DataKey {
     RoyaltyBps(u128),
     Uri(u128),
     FanPoints(Address),
+    CoCreator(u128),
+    AdminPubKey,
+    UsedAdminNonce(u128),
+    UsedTransferNonce(Address, u128),
+    CallCount(Symbol),
+    TrustedSource,
+    Exported(u128),
+    Imported(Address, u128),
+    // (owner, operator) -> ledger cuối cùng approval còn hiệu lực
+    OperatorApproval(Address, Address),
+    TokenAcl(u128),
+    TokenAclEnabled(u128),
+    MintTimestamp(u128),
+    FanJoinLedger(Address),
+    // (tuần, trang) -> thành viên, lưu ở persistent storage
+    CohortIndex(u32, u32),
+    BackupUri(u128),
+    AllowBackup(Address),
+    PurchaseForm(u128),
+    FormResponse(u128, Address),
+    Conversion,
+    ConversionRoundId,
+    ConvertedPoints(u32, Address),
+    // (endorser, endorsee)
+    Endorsement(Address, Address),
+    EndorsementCount(Address),
+    EndorseMinPoints,
+    EndorseMultiplierBps,
+    CrossBundle(Bytes),
+    CrossBundleNonce,
+    // Ô thứ (số thứ tự % ADMIN_LOG_CAP) của ring buffer, lưu ở persistent storage
+    AdminLog(u32),
+    AdminLogCount,
+    UriVariant(u128, Symbol),
+    UriLangs(u128),
+    ExternalLinks(u128),
+    LinkPlatforms,
+    ProvenanceHash(u128),
+    MintedTo(u128),
+    MintingWindow(Address),
+    WindowExempt(Address),
+    // old -> new, chỉ một bước
+    CreatorRedirect(Address),
+    // new -> các địa chỉ cũ đã chuyển quyền creator sang new
+    RedirectSources(Address),
+    MetadataFrozen(u128),
+    HybridSwap(Bytes),
+    MembershipBenefits(u128),
+    // Kho tham số chung, giá trị là bytes thô (xem bảng tham số bên dưới)
+    Param(Bytes),
+}
+
+// DataKey gần chạm giới hạn 50 biến thể của contract spec, các key mới cho
+// phần mua bán/phân phối token đặt ở đây.
+#[contracttype]
+#[derive(Clone)]
+pub enum MarketKey {
+    Claim(u64),
+    ClaimNonce,
+    ClaimCount(u64, Address),
+    ClaimPoints,
+    Locked(u128),
+    CreatorMinListingFee(Address),
+    Retired(u128),
+    DustThreshold(Address),
+    LastRoyaltyPayment(u128),
+    TotalRoyaltyPaid(u128),
+    EmergencyTransfers,
+    MaxEmergencyTransfers,
+    StatementPeriod,
+    PeriodClosedAt,
+    Statement(Address, u64),
+    HybridSwapNonce,
+    MemberTokens(Address),
+    CreatorTokens(Address),
+    Airdrop(u64),
+    AirdropNonce,
+    // creator -> số điểm an ủi khi fan trả token; có key = đang nhận trả lại
+    AcceptReturns(Address),
+    TipsReceivedInTokens(Address),
+    OperatorSuccessors,
+    Promos(Address),
+    PromoNonce,
+    SharedControl(u128),
+    ActionApprovals(u128, BytesN<32>),
+    // Giây giữ royalty trong contract trước khi creator được rút, 0 = trả ngay
+    RoyaltyHoldPeriod,
+    PendingRoyaltyRelease(u128),
+    SnapshotAccumulator,
+    SnapshotLock,
+    OwnerRoot(u64),
+    OwnerRootNonce,
+    // Khoá ed25519 owner đăng ký để ký thao tác off-chain
+    SigningKey(Address),
+    ExclusivityPeriod(Address),
+    ExclusivityEnd(u128),
+    // token -> ledger cuối cùng mà miễn royalty một lần còn hiệu lực
+    RoyaltyWaiver(u128),
+    WaiverTtl,
+    EntrypointDisabled(Symbol),
+}
+
+// Key cho điểm fan (giới hạn, tổng toàn hệ thống), tách khỏi DataKey vì lý do trên
+#[contracttype]
+#[derive(Clone)]
+pub enum FanKey {
+    MaxFanPointsPerAddress,
+    MaxTotalFanPoints,
+    // Tổng điểm đang lưu hành, tính từ khi có giới hạn toàn hệ thống
+    TotalFanPoints,
+    // Tip bằng token: mỗi `divisor` đơn vị được 1 điểm, 0 = không thưởng điểm
+    TipPointsDivisor,
+    BurnedPoints(Address),
+    TotalBurnedPoints,
+    // Vec<(fan, tổng đã đốt)> giảm dần, tối đa BURN_LEADERBOARD_SIZE
+    BurnLeaderboard,
+    // Cho phép fan chuyển điểm cho nhau (hiện chỉ qua gift_with_points)
+    PointsTransferable,
+    // Điểm thưởng cho seller khi bán qua buy, tính theo bps của giá
+    SellerPointsRate,
+    // key idempotency của award_fan_points -> ledger ghi nhận
+    GrantKey(BytesN<32>),
+    GrantKeyRetention,
+    // Granter được phép nộp voucher tiêu điểm thay fan
+    Granters,
+    UsedSpendNonce(Address, u64),
+    // Số thành viên của cohort theo tuần
+    CohortSize(u32),
+    // Tổng điểm hiện có của các fan trong cohort
+    CohortPoints(u32),
+}
+
+// Key cho vai trò admin và hội đồng multi-sig, tách khỏi DataKey vì lý do trên
+#[contracttype]
+#[derive(Clone)]
+pub enum AdminKey {
+    Admin,
+    // Timestamp hết hạn quyền admin; quá hạn thì chỉ hội đồng thay đổi được state
+    AdminExpiry,
+    Council,
+    GovProposal(u64),
+    GovProposalNonce,
+    // Quyền tự động hoá giới hạn của một bot
+    Automation(Address),
 }
 
+// ===========================
+// Param registry
+// ===========================
+//
+// Các tham số mới được lưu dưới DataKey::Param(key) thay vì thêm biến thể
+// DataKey riêng. Giá trị là bytes thô, giải mã theo quy ước:
+//
+//   key                 định dạng giá trị
+//   ------------------  ------------------------------------------------
+//   (chưa có tham số)   -
+//
+// Quy ước chung:
+//   - số nguyên: big-endian, độ dài cố định (u32 = 4 bytes, u128 = 16 bytes)
+//   - Address: XDR (Address::to_xdr)
+//   - cờ bool: 1 byte, 0x00 = false, 0x01 = true
+//   - chuỗi: UTF-8
+//
+// Khi một tham số cần kiểm tra kiểu chặt chẽ, chuyển nó sang một biến thể
+// DataKey riêng.
+
 // ===========================
 // Errors
 // ===========================
 
 #[contracterror]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Error {
     NotAuthorized = 1,
     TokenNotFound = 2,
@@ -37,6 +233,47 @@ pub enum Error {
     NotOwner = 20,
     SameOwner = 21,
     PaymentFailed = 22,
+    InvalidSplit = 23,
+    InvalidSignature = 24,
+    NonceUsed = 25,
+    UnknownAdminOp = 26,
+    UntrustedSource = 27,
+    InvalidTicket = 28,
+    AlreadyImported = 29,
+    FormResponseRequired = 30,
+    InsufficientPoints = 31,
+    NotActive = 32,
+    BudgetExhausted = 33,
+    CapExceeded = 34,
+    RoundsToZero = 35,
+    AlreadyActive = 36,
+    AlreadyEndorsed = 37,
+    InvalidLink = 38,
+    InvalidWindow = 39,
+    TokenLocked = 40,
+    InvalidRedirect = 41,
+    PointsNotTransferable = 42,
+    InsufficientApprovals = 43,
+    DuplicateGrant = 44,
+    VoucherExpired = 45,
+    SigningKeyNotRegistered = 46,
+    // Lỗi kiểm tra đầu vào
+    ZeroAmount = 47,
+    SelfAddress = 48,
+    ContractAddress = 49,
+    BpsOutOfRange = 50,
+    EmptyInput = 51,
+    EntrypointDisabled = 52,
+    TimelockActive = 53,
+    AutomationLimitExceeded = 54,
+    DuplicateToken = 55,
+    NotInAcl = 60,
+    AdminKeyExpired = 62,
+    TokenRetired = 63,
+    OutsideMintWindow = 64,
+    MetadataFrozen = 65,
+    GlobalPointCapReached = 66,
+    ExclusivityPeriodActive = 69,
 }
 
 // ===========================
@@ -55,6 +292,363 @@ pub struct NftInfo {
     pub creator: Address,
     pub royalty_bps: u32,
     pub uri: Bytes,
+    pub minted_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct CoCreatorInfo {
+    pub co_creator: Address,
+    // phần royalty của creator chính (creator_a), phần còn lại thuộc co_creator
+    pub split_a_bps: u32,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct ExternalLink {
+    pub platform: Bytes,
+    pub url: Bytes,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct Promo {
+    pub promo_id: u64,
+    pub discount_bps: u32,
+    pub start_ledger: u32,
+    pub end_ledger: u32,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct MintWindow {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct ExtendedNftInfo {
+    pub info: NftInfo,
+    pub co_creator: Option<Address>,
+    pub split_a_bps: u32,
+    pub backup_uri: Option<Bytes>,
+    pub external_links: Vec<ExternalLink>,
+}
+
+// Trạng thái gộp của một token cho indexer; uri/backup_uri/external_links
+// để trống khi dump với include_uris = false
+#[contracttype]
+#[derive(Clone)]
+pub struct TokenDump {
+    pub token_id: u128,
+    pub owner: Address,
+    pub creator: Address,
+    pub royalty_bps: u32,
+    pub minted_at: u64,
+    pub co_creator: Option<Address>,
+    pub split_a_bps: u32,
+    pub uri: Option<Bytes>,
+    pub backup_uri: Option<Bytes>,
+    pub external_links: Vec<ExternalLink>,
+    pub locked: bool,
+    pub retired: bool,
+    pub metadata_frozen: bool,
+    // Lần trả royalty gần nhất (xem RoyaltyPayment); trải phẳng vì SDK không
+    // chuyển được Option<struct> lồng trong struct khi chạy testutils
+    pub last_royalty_buyer: Option<Address>,
+    pub last_royalty_paid_to: Option<Address>,
+    pub last_royalty_amount: i128,
+    pub last_royalty_ledger: u64,
+    pub total_royalty_paid: i128,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct ConfigDump {
+    pub default_payment_token: Option<Address>,
+    pub last_token_id: u128,
+    pub statement_period: u64,
+    pub royalty_hold_period: u64,
+    pub waiver_ttl: u32,
+    pub max_fan_points_per_address: u128,
+    pub max_total_fan_points: u128,
+    pub total_fan_points: u128,
+    pub seller_points_rate: u32,
+    pub tip_points_divisor: u128,
+    pub points_transferable: bool,
+    pub grant_key_retention: u32,
+    pub snapshot_locked: bool,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct ExportTicket {
+    pub source_contract: Address,
+    pub destination_contract: Address,
+    pub token_id: u128,
+    pub owner: Address,
+    pub creator: Address,
+    pub co_creator: Option<Address>,
+    pub split_a_bps: u32,
+    pub royalty_bps: u32,
+    pub uri: Bytes,
+    pub uri_variants: Vec<(Symbol, Bytes)>,
+    pub metadata_frozen: bool,
+    pub retired: bool,
+    pub commitment: BytesN<32>,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct PurchaseForm {
+    pub form_uri: Bytes,
+    pub required: bool,
+}
+
+// Chỉ lưu cam kết (hash) của câu trả lời off-chain, không bao giờ lưu nội dung
+#[contracttype]
+#[derive(Clone)]
+pub struct FormResponse {
+    pub response_hash: BytesN<32>,
+    pub fulfilled: bool,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct ConversionRound {
+    pub round: u32,
+    pub funder: Address,
+    pub token: Address,
+    pub remaining: i128,
+    pub rate_points_per_unit: u128,
+    // số điểm tối đa mỗi fan được đổi trong vòng này
+    pub max_per_fan: u128,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct CrossBundle {
+    pub initiator: Address,
+    pub token_ids: Vec<u128>,
+    pub price: i128,
+    pub payment_token: Address,
+    pub required_approvals: Vec<Address>,
+    pub approvals_received: Vec<Address>,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct HybridSwap {
+    pub offerer: Address,
+    pub token_a: u128,
+    pub cash: i128,
+    pub payment_token: Address,
+    pub token_b: u128,
+    // Bản ghi bị xoá khi chấp nhận hoặc huỷ, nên đề nghị còn lưu luôn là None
+    pub accepter: Option<Address>,
+    pub expires_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub enum LogSubject {
+    None,
+    Address(Address),
+    Token(u128),
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct AdminLogEntry {
+    pub action: Symbol,
+    pub actor: Address,
+    pub subject: LogSubject,
+    pub ledger: u32,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct OperatorSuccessor {
+    pub old_operator: Address,
+    pub new_operator: Address,
+    pub until_ledger: u32,
+}
+
+// Token đồng sở hữu: owner lưu là địa chỉ contract và token bị khoá;
+// mọi hành động cần tổng cổ phần đồng ý vượt threshold_bps.
+#[contracttype]
+#[derive(Clone)]
+pub struct SharedControl {
+    pub holders: Vec<(Address, u32)>,
+    pub threshold_bps: u32,
+    // Các action_hash đang có phiếu, để xoá khi thoát chế độ đồng sở hữu
+    pub pending: Vec<BytesN<32>>,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct SnapshotAccumulator {
+    pub next_id: u128,
+    // Token id lớn nhất lúc bắt đầu; token mint sau đó không nằm trong snapshot
+    pub last_id: u128,
+    pub hash: BytesN<32>,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct OwnerRoot {
+    pub root: BytesN<32>,
+    pub ledger: u32,
+    pub token_count: u128,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct SpendVoucher {
+    pub fan: Address,
+    pub points: u128,
+    pub purpose: Symbol,
+    pub nonce: u64,
+    pub expiry_ledger: u32,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct Airdrop {
+    pub creator: Address,
+    pub points_per_token: u128,
+    // Danh sách token của creator chốt lúc tạo; token mint sau đó không được tính
+    // và việc chuyển quyền creator giữa chừng không làm mất thứ tự
+    pub tokens: Vec<u128>,
+    pub snapshot_len: u32,
+    pub cursor: u32,
+}
+
+// Đợt nhận token miễn phí (giá 0), khác với bán
+#[contracttype]
+#[derive(Clone)]
+pub struct Claim {
+    pub creator: Address,
+    pub token_ids: Vec<u128>,
+    pub next_index: u32,
+    pub max_per_wallet: u32,
+    pub start_ledger: u32,
+    pub end_ledger: u32,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct PendingRelease {
+    pub amount: i128,
+    pub release_at: u64,
+    pub payment_token: Address,
+    pub creator: Address,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct RoyaltyPayment {
+    pub buyer: Address,
+    pub amount: i128,
+    pub paid_to: Address,
+    pub at_ledger: u64,
+}
+
+// Hội đồng multi-sig: đề xuất cần `threshold` chữ ký và chờ `timelock` giây
+#[contracttype]
+#[derive(Clone)]
+pub struct Council {
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+    pub timelock: u64,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub enum GovAction {
+    // (admin mới, nhiệm kỳ tính bằng giây)
+    SetAdmin(Address, u64),
+    // (token, người nhận, lý do)
+    EmergencyTransfer(u128, Address, Bytes),
+    // Giới hạn số lần chuyển cưỡng chế trọn đời
+    SetMaxEmergencyTransfers(u32),
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct GovProposal {
+    pub action: GovAction,
+    pub proposer: Address,
+    pub approvals: Vec<Address>,
+    pub created_at: u64,
+}
+
+// Bot chỉ được gọi setter `setter` với giá trị trong [min, max]
+#[contracttype]
+#[derive(Clone)]
+pub struct AutomationPermission {
+    pub setter: Symbol,
+    pub min: u128,
+    pub max: u128,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct EmergencyRecord {
+    pub token_id: u128,
+    pub from: Address,
+    pub to: Address,
+    pub reason: Bytes,
+    pub at: u64,
+}
+
+// Sao kê thu nhập của creator trong một kỳ. Số tiền được cộng thẳng theo
+// đơn vị của payment token từng lần bán.
+#[contracttype]
+#[derive(Clone)]
+pub struct Statement {
+    pub royalty_earned: i128,
+    pub primary_sales: i128,
+    pub secondary_sales: i128,
+    pub tips: i128,
+    pub fees_paid: i128,
+    // số lần bán token của creator được ghi vào kỳ
+    pub per_token_breakdown_count: u32,
+}
+
+// Contract spec không có u8/u16 nên các trường nhỏ dùng u32
+#[contracttype]
+#[derive(Clone)]
+pub struct Benefits {
+    pub discount_bps: u32,
+    pub early_access_days: u32,
+    pub max_airdrop_eligible: u32,
+    pub priority_queue_position: u32,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct Membership {
+    pub tier_level: u32,
+    pub benefits: Benefits,
+    pub expires_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct BuySimulation {
+    pub royalty: i128,
+    pub seller_amount: i128,
+    pub platform_fee: i128,
+    pub points_awarded: u128,
+    pub points_redeemed: u128,
+    pub effective_price: i128,
+    // Khuyến mãi đã áp dụng; promo_skipped = có khuyến mãi nhưng giá sẽ dưới mức tối thiểu
+    pub promo_bps: u32,
+    pub promo_skipped: bool,
+    // buy sẽ cần form_response_hash
+    pub form_required: bool,
 }
 
 // ===========================
@@ -66,139 +660,3701 @@ pub struct FanRewardsNftMarket;
 
 #[contractimpl]
 impl FanRewardsNftMarket {
-    pub fn set_default_payment_token(env: Env, admin: Address, token: Address) {
+    // Gọi một lần sau khi deploy. Quyền admin hết hạn sau `term` giây và phải
+    // được gia hạn bằng renew_admin.
+    pub fn init(env: Env, admin: Address, term: u64) -> Result<(), Error> {
         admin.require_auth();
+        if env.storage().instance().has(&AdminKey::Admin) {
+            return Err(Error::AlreadyActive);
+        }
+        if term == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        write_admin(&env, &admin, term)?;
+        log_admin_action(&env, symbol_short!("init"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    pub fn get_admin(env: Env) -> Option<Address> {
         env.storage()
             .instance()
-            .set::<DataKey, Address>(&DataKey::DefaultPayToken, &token);
+            .get::<AdminKey, Address>(&AdminKey::Admin)
     }
 
-    pub fn get_default_payment_token(env: Env) -> Option<Address> {
+    pub fn get_admin_expiry(env: Env) -> u64 {
         env.storage()
             .instance()
-            .get::<DataKey, Address>(&DataKey::DefaultPayToken)
+            .get::<AdminKey, u64>(&AdminKey::AdminExpiry)
+            .unwrap_or(0)
     }
 
-    pub fn mint(
-        env: Env,
-        creator: Address,
-        initial_owner: Address,
-        royalty_bps: u32,
-        uri: Bytes,
-    ) -> Result<TokenId, Error> {
-        creator.require_auth();
-        if royalty_bps > 10_000 {
-            return Err(Error::InvalidRoyalty);
+    // Chỉ admin còn hạn mới gia hạn được; trả về thời điểm hết hạn mới
+    pub fn renew_admin(env: Env, current_admin: Address, extension: u64) -> Result<u64, Error> {
+        require_admin(&env, &current_admin)?;
+        if extension == 0 {
+            return Err(Error::ZeroAmount);
         }
+        let expiry = write_admin(&env, &current_admin, extension)?;
+        log_admin_action(
+            &env,
+            symbol_short!("renew"),
+            &current_admin,
+            LogSubject::None,
+        );
+        Ok(expiry)
+    }
 
-        let id = next_id(&env)?;
-        let tid = TokenId(id);
+    pub fn set_council(
+        env: Env,
+        admin: Address,
+        signers: Vec<Address>,
+        threshold: u32,
+        timelock: u64,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        if signers.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        if signers.len() > MAX_COUNCIL_SIGNERS {
+            return Err(Error::CapExceeded);
+        }
+        let mut seen: Vec<Address> = Vec::new(&env);
+        for signer in signers.iter() {
+            if seen.contains(&signer) {
+                return Err(Error::InvalidSplit);
+            }
+            seen.push_back(signer);
+        }
+        if threshold == 0 || threshold > signers.len() {
+            return Err(Error::InvalidSplit);
+        }
+        env.storage().instance().set::<AdminKey, Council>(
+            &AdminKey::Council,
+            &Council {
+                signers,
+                threshold,
+                timelock,
+            },
+        );
+        log_admin_action(&env, symbol_short!("council"), &admin, LogSubject::None);
+        Ok(())
+    }
 
-        set_owner(&env, id, &initial_owner);
-        set_creator(&env, id, &creator);
-        set_royalty_bps(&env, id, royalty_bps);
-        set_uri(&env, id, &uri);
+    pub fn get_council(env: Env) -> Option<Council> {
+        env.storage()
+            .instance()
+            .get::<AdminKey, Council>(&AdminKey::Council)
+    }
 
-        Ok(tid)
+    // Thành viên hội đồng đề xuất; chữ ký của người đề xuất được tính luôn
+    pub fn propose_gov_action(env: Env, signer: Address, action: GovAction) -> Result<u64, Error> {
+        require_council_signer(&env, &signer)?;
+        validate_gov_action(&env, &action)?;
+        let mut approvals: Vec<Address> = Vec::new(&env);
+        approvals.push_back(signer.clone());
+        store_gov_proposal(&env, &signer, action, approvals)
     }
 
-    pub fn get_info(env: Env, token_id: TokenId) -> Result<NftInfo, Error> {
-        let id = token_id.0;
-        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
-        let creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
-        let royalty_bps = get_royalty_bps(&env, id).ok_or(Error::TokenNotFound)?;
-        let uri = get_uri(&env, id).ok_or(Error::TokenNotFound)?;
+    pub fn approve_gov_action(env: Env, signer: Address, proposal_id: u64) -> Result<(), Error> {
+        require_council_signer(&env, &signer)?;
+        let key = AdminKey::GovProposal(proposal_id);
+        let mut proposal = env
+            .storage()
+            .instance()
+            .get::<AdminKey, GovProposal>(&key)
+            .ok_or(Error::NotActive)?;
+        if !proposal.approvals.contains(&signer) {
+            proposal.approvals.push_back(signer.clone());
+            env.storage()
+                .instance()
+                .set::<AdminKey, GovProposal>(&key, &proposal);
+        }
+        env.events()
+            .publish((symbol_short!("gov_appr"), proposal_id), signer);
+        Ok(())
+    }
 
-        Ok(NftInfo {
-            token_id,
-            owner,
-            creator,
-            royalty_bps,
-            uri,
-        })
+    // Người đề xuất rút lại đề xuất chưa thực thi
+    pub fn cancel_gov_action(env: Env, signer: Address, proposal_id: u64) -> Result<(), Error> {
+        signer.require_auth();
+        let key = AdminKey::GovProposal(proposal_id);
+        let proposal = env
+            .storage()
+            .instance()
+            .get::<AdminKey, GovProposal>(&key)
+            .ok_or(Error::NotActive)?;
+        if proposal.proposer != signer {
+            return Err(Error::NotAuthorized);
+        }
+        env.storage().instance().remove(&key);
+        env.events()
+            .publish((symbol_short!("gov_cncl"), proposal_id), signer);
+        Ok(())
     }
 
-    pub fn transfer(env: Env, token_id: TokenId, from: Address, to: Address) -> Result<(), Error> {
-        let id = token_id.0;
-        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
-        if owner != from {
-            return Err(Error::NotOwner);
+    // Ai cũng có thể thực thi khi đủ chữ ký của các thành viên hiện tại và đã
+    // qua timelock tính từ lúc đề xuất.
+    pub fn execute_gov_action(env: Env, proposal_id: u64) -> Result<(), Error> {
+        let key = AdminKey::GovProposal(proposal_id);
+        let proposal = env
+            .storage()
+            .instance()
+            .get::<AdminKey, GovProposal>(&key)
+            .ok_or(Error::NotActive)?;
+        let council = env
+            .storage()
+            .instance()
+            .get::<AdminKey, Council>(&AdminKey::Council)
+            .ok_or(Error::NotActive)?;
+        let approved = proposal
+            .approvals
+            .iter()
+            .filter(|a| council.signers.contains(a))
+            .count() as u32;
+        if approved < council.threshold {
+            return Err(Error::InsufficientApprovals);
         }
-        from.require_auth();
-        if from == to {
-            return Err(Error::SameOwner);
+        let ready_at = proposal
+            .created_at
+            .checked_add(council.timelock)
+            .ok_or(Error::Overflow)?;
+        if env.ledger().timestamp() < ready_at {
+            return Err(Error::TimelockActive);
         }
-        set_owner(&env, id, &to);
+        validate_gov_action(&env, &proposal.action)?;
+
+        env.storage().instance().remove(&key);
+        let actor = env.current_contract_address();
+        match proposal.action {
+            GovAction::SetAdmin(new_admin, term) => {
+                write_admin(&env, &new_admin, term)?;
+                log_admin_action(
+                    &env,
+                    symbol_short!("gov_admin"),
+                    &actor,
+                    LogSubject::Address(new_admin),
+                );
+            }
+            GovAction::EmergencyTransfer(id, to, reason) => {
+                let from = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+                set_owner(&env, id, &to);
+                let mut records = emergency_records(&env);
+                records.push_back(EmergencyRecord {
+                    token_id: id,
+                    from: from.clone(),
+                    to: to.clone(),
+                    reason: reason.clone(),
+                    at: env.ledger().timestamp(),
+                });
+                env.storage()
+                    .instance()
+                    .set::<MarketKey, Vec<EmergencyRecord>>(
+                        &MarketKey::EmergencyTransfers,
+                        &records,
+                    );
+                env.events()
+                    .publish((symbol_short!("emergency"), id), (from, to, reason));
+                log_admin_action(
+                    &env,
+                    symbol_short!("emergency"),
+                    &actor,
+                    LogSubject::Token(id),
+                );
+            }
+            GovAction::SetMaxEmergencyTransfers(max) => {
+                env.storage()
+                    .instance()
+                    .set::<MarketKey, u32>(&MarketKey::MaxEmergencyTransfers, &max);
+                log_admin_action(&env, symbol_short!("emerg_max"), &actor, LogSubject::None);
+            }
+        }
+        env.events()
+            .publish((symbol_short!("gov_exec"), proposal_id), ());
         Ok(())
     }
 
-    pub fn get_fan_points(env: Env, fan: Address) -> u128 {
+    pub fn get_gov_proposal(env: Env, proposal_id: u64) -> Option<GovProposal> {
         env.storage()
             .instance()
-            .get::<DataKey, u128>(&DataKey::FanPoints(fan))
-            .unwrap_or(0u128)
+            .get::<AdminKey, GovProposal>(&AdminKey::GovProposal(proposal_id))
     }
 
-    pub fn award_fan_points(
+    // Cấp cho bot quyền gọi một số setter trong giới hạn; ghi đè quyền cũ
+    pub fn grant_automation(
         env: Env,
-        granter: Address,
-        fan: Address,
-        points: u128,
+        admin: Address,
+        bot: Address,
+        permissions: Vec<AutomationPermission>,
     ) -> Result<(), Error> {
-        granter.require_auth();
-        let current: u128 = env
-            .storage()
-            .instance()
-            .get::<DataKey, u128>(&DataKey::FanPoints(fan.clone()))
-            .unwrap_or(0u128);
-        let new_total: u128 = current.checked_add(points).ok_or(Error::Overflow)?;
+        require_admin(&env, &admin)?;
+        if bot == admin {
+            return Err(Error::SelfAddress);
+        }
+        ensure_not_this_contract(&env, &bot)?;
+        if permissions.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        let mut seen: Vec<Symbol> = Vec::new(&env);
+        for p in permissions.iter() {
+            if !automatable_setters(&env).contains(&p.setter) {
+                return Err(Error::UnknownAdminOp);
+            }
+            if seen.contains(&p.setter) {
+                return Err(Error::DuplicateGrant);
+            }
+            if p.min > p.max {
+                return Err(Error::InvalidWindow);
+            }
+            seen.push_back(p.setter);
+        }
         env.storage()
             .instance()
-            .set::<DataKey, u128>(&DataKey::FanPoints(fan), &new_total);
+            .set::<AdminKey, Vec<AutomationPermission>>(
+                &AdminKey::Automation(bot.clone()),
+                &permissions,
+            );
+        log_admin_action(
+            &env,
+            symbol_short!("auto_set"),
+            &admin,
+            LogSubject::Address(bot),
+        );
         Ok(())
     }
 
-    pub fn buy(
-        env: Env,
-        token_id: TokenId,
-        buyer: Address,
-        price: i128,
-        payment_token: Option<Address>,
-    ) -> Result<(), Error> {
-        buyer.require_auth();
-        if price <= 0 {
-            return Err(Error::InvalidPrice);
+    pub fn revoke_automation(env: Env, admin: Address, bot: Address) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        let key = AdminKey::Automation(bot.clone());
+        if !env.storage().instance().has(&key) {
+            return Err(Error::NotActive);
         }
+        env.storage().instance().remove(&key);
+        log_admin_action(
+            &env,
+            symbol_short!("auto_rev"),
+            &admin,
+            LogSubject::Address(bot),
+        );
+        Ok(())
+    }
 
-        let id = token_id.0;
-        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
-        if owner == buyer {
-            return Err(Error::SameOwner);
-        }
-        let creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
-        let royalty_bps = get_royalty_bps(&env, id).ok_or(Error::TokenNotFound)?;
+    pub fn get_automation(env: Env, bot: Address) -> Vec<AutomationPermission> {
+        env.storage()
+            .instance()
+            .get::<AdminKey, Vec<AutomationPermission>>(&AdminKey::Automation(bot))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn set_default_payment_token(
+        env: Env,
+        admin: Address,
+        token: Address,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        write_default_payment_token(&env, &token);
+        log_admin_action(
+            &env,
+            symbol_short!("pay_token"),
+            &admin,
+            LogSubject::Address(token),
+        );
+        Ok(())
+    }
+
+    pub fn get_default_payment_token(env: Env) -> Option<Address> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::DefaultPayToken)
+    }
+
+    pub fn set_param(env: Env, admin: Address, key: Bytes, value: Bytes) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        write_param(&env, key, &value);
+        log_admin_action(&env, symbol_short!("set_param"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    pub fn get_param(env: Env, key: Bytes) -> Option<Bytes> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Bytes>(&DataKey::Param(key))
+    }
+
+    pub fn set_admin_public_key(
+        env: Env,
+        admin: Address,
+        public_key: BytesN<32>,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        // Admin đang giữ quyền đặt hoặc thay key; key gắn với địa chỉ admin nên
+        // admin mới (qua hội đồng) phải đặt key của mình, key cũ hết hiệu lực.
+        env.storage()
+            .instance()
+            .set::<DataKey, (Address, BytesN<32>)>(
+                &DataKey::AdminPubKey,
+                &(admin.clone(), public_key),
+            );
+        log_admin_action(&env, symbol_short!("admin_key"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    // Chỉ trả về key còn gắn với admin hiện tại
+    pub fn get_admin_public_key(env: Env) -> Option<BytesN<32>> {
+        let admin = Self::get_admin(env.clone())?;
+        admin_public_key(&env)
+            .filter(|(key_admin, _)| *key_admin == admin)
+            .map(|(_, key)| key)
+    }
+
+    // Admin ký thao tác off-chain, relayer bất kỳ gửi lên chain.
+    // Chữ ký ed25519 trên (op_type || params || nonce || contract_id).
+    //
+    //   op_type                      params (XDR)
+    //   ---------------------------  ----------------
+    //   "set_default_payment_token"  Address
+    //   "set_param"                  (Bytes, Bytes)
+    //   "rotate_admin_key"           BytesN<32>
+    pub fn execute_signed_admin_op(
+        env: Env,
+        op_type: Bytes,
+        params: Bytes,
+        nonce: u128,
+        signature: Bytes,
+    ) -> Result<(), Error> {
+        // Admin hết hạn thì chỉ hội đồng thay đổi được state, kể cả qua chữ ký
+        let admin = active_admin(&env)?;
+        let (key_admin, public_key) = admin_public_key(&env).ok_or(Error::NotAuthorized)?;
+        if key_admin != admin {
+            return Err(Error::NotAuthorized);
+        }
+        if env
+            .storage()
+            .instance()
+            .has(&DataKey::UsedAdminNonce(nonce))
+        {
+            return Err(Error::NonceUsed);
+        }
+        let signature: BytesN<64> = signature.try_into().map_err(|_| Error::InvalidSignature)?;
+
+        let mut message = op_type.clone();
+        message.append(&params);
+        message.extend_from_array(&nonce.to_be_bytes());
+        message.append(&env.current_contract_address().to_xdr(&env));
+        // ed25519_verify panic nếu chữ ký sai
+        env.crypto()
+            .ed25519_verify(&public_key, &message, &signature);
+
+        env.storage()
+            .instance()
+            .set::<DataKey, bool>(&DataKey::UsedAdminNonce(nonce), &true);
+
+        // Thao tác ký off-chain không có Address của admin, ghi actor là chính contract
+        let actor = env.current_contract_address();
+        if op_type == Bytes::from_slice(&env, b"set_default_payment_token") {
+            let token = Address::from_xdr(&env, &params).map_err(|_| Error::UnknownAdminOp)?;
+            write_default_payment_token(&env, &token);
+            log_admin_action(
+                &env,
+                symbol_short!("pay_token"),
+                &actor,
+                LogSubject::Address(token),
+            );
+        } else if op_type == Bytes::from_slice(&env, b"set_param") {
+            let (key, value) =
+                <(Bytes, Bytes)>::from_xdr(&env, &params).map_err(|_| Error::UnknownAdminOp)?;
+            write_param(&env, key, &value);
+            log_admin_action(&env, symbol_short!("set_param"), &actor, LogSubject::None);
+        } else if op_type == Bytes::from_slice(&env, b"rotate_admin_key") {
+            let new_key =
+                BytesN::<32>::from_xdr(&env, &params).map_err(|_| Error::UnknownAdminOp)?;
+            env.storage()
+                .instance()
+                .set::<DataKey, (Address, BytesN<32>)>(&DataKey::AdminPubKey, &(admin, new_key));
+            log_admin_action(&env, symbol_short!("admin_key"), &actor, LogSubject::None);
+        } else {
+            return Err(Error::UnknownAdminOp);
+        }
+        Ok(())
+    }
+
+    pub fn mint(
+        env: Env,
+        creator: Address,
+        initial_owner: Address,
+        royalty_bps: u32,
+        uri: Bytes,
+    ) -> Result<TokenId, Error> {
+        creator.require_auth();
+        if uri.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        check_mint_window(&env, &creator)?;
+        let id = mint_token(&env, &creator, &initial_owner, royalty_bps, &uri)?;
+        Ok(TokenId(id))
+    }
+
+    // Chuyển quyền creator của toàn bộ catalogue sang địa chỉ mới. Royalty và các
+    // hàm chỉ dành cho creator đi qua redirect ngay lập tức; entry Creator của
+    // từng token được ghi lại dần bằng materialize_creatorship.
+    pub fn transfer_creatorship(env: Env, old: Address, new: Address) -> Result<(), Error> {
+        old.require_auth();
+        new.require_auth();
+        // old chưa chuyển đi và new phải là creator đang hoạt động (chưa chuyển
+        // sang nơi khác), nên không thể tạo vòng lặp
+        if old == new
+            || env
+                .storage()
+                .instance()
+                .has(&DataKey::CreatorRedirect(old.clone()))
+            || env
+                .storage()
+                .instance()
+                .has(&DataKey::CreatorRedirect(new.clone()))
+        {
+            return Err(Error::InvalidRedirect);
+        }
+        // Cấu hình riêng của creator được chuyển sang new; nếu new đã có cấu hình
+        // cùng loại thì từ chối thay vì tự chọn bên nào thắng
+        if both_set(
+            &env,
+            &MarketKey::CreatorMinListingFee(old.clone()),
+            &MarketKey::CreatorMinListingFee(new.clone()),
+        ) || both_set(
+            &env,
+            &MarketKey::Promos(old.clone()),
+            &MarketKey::Promos(new.clone()),
+        ) || both_set(
+            &env,
+            &MarketKey::ExclusivityPeriod(old.clone()),
+            &MarketKey::ExclusivityPeriod(new.clone()),
+        ) || both_set(
+            &env,
+            &MarketKey::AcceptReturns(old.clone()),
+            &MarketKey::AcceptReturns(new.clone()),
+        ) || both_set(
+            &env,
+            &DataKey::AllowBackup(old.clone()),
+            &DataKey::AllowBackup(new.clone()),
+        ) || both_set(
+            &env,
+            &DataKey::MintingWindow(old.clone()),
+            &DataKey::MintingWindow(new.clone()),
+        ) || both_set(
+            &env,
+            &DataKey::WindowExempt(old.clone()),
+            &DataKey::WindowExempt(new.clone()),
+        ) {
+            return Err(Error::InvalidRedirect);
+        }
+        move_setting::<_, i128>(
+            &env,
+            &MarketKey::CreatorMinListingFee(old.clone()),
+            &MarketKey::CreatorMinListingFee(new.clone()),
+        );
+        move_setting::<_, Vec<Promo>>(
+            &env,
+            &MarketKey::Promos(old.clone()),
+            &MarketKey::Promos(new.clone()),
+        );
+        move_setting::<_, u64>(
+            &env,
+            &MarketKey::ExclusivityPeriod(old.clone()),
+            &MarketKey::ExclusivityPeriod(new.clone()),
+        );
+        move_setting::<_, u128>(
+            &env,
+            &MarketKey::AcceptReturns(old.clone()),
+            &MarketKey::AcceptReturns(new.clone()),
+        );
+        move_setting::<_, bool>(
+            &env,
+            &DataKey::AllowBackup(old.clone()),
+            &DataKey::AllowBackup(new.clone()),
+        );
+        move_setting::<_, Vec<MintWindow>>(
+            &env,
+            &DataKey::MintingWindow(old.clone()),
+            &DataKey::MintingWindow(new.clone()),
+        );
+        move_setting::<_, bool>(
+            &env,
+            &DataKey::WindowExempt(old.clone()),
+            &DataKey::WindowExempt(new.clone()),
+        );
+
+        // Gộp chuỗi về một bước: các địa chỉ đã chuyển sang old (A→old) giờ trỏ
+        // thẳng tới new, để resolve_creator chỉ cần đọc một redirect
+        let mut sources = redirect_sources(&env, &new);
+        for source in redirect_sources(&env, &old).iter() {
+            env.storage()
+                .instance()
+                .set::<DataKey, Address>(&DataKey::CreatorRedirect(source.clone()), &new);
+            sources.push_back(source);
+        }
+        env.storage()
+            .instance()
+            .remove(&DataKey::RedirectSources(old.clone()));
+        env.storage()
+            .instance()
+            .set::<DataKey, Address>(&DataKey::CreatorRedirect(old.clone()), &new);
+        sources.push_back(old.clone());
+        // Chuyển index token của old sang new để airdrop theo creator vẫn đủ;
+        // token collab giữa old và new chỉ giữ một lần. Royalty đang giữ chưa
+        // rút của old cũng thuộc về new.
+        for id in creator_tokens(&env, &old).iter() {
+            index_creator_token(&env, &new, id);
+            let mut pending = pending_royalties(&env, id);
+            let mut moved = false;
+            for i in 0..pending.len() {
+                let mut hold = pending.get_unchecked(i);
+                if hold.creator == old {
+                    hold.creator = new.clone();
+                    pending.set(i, hold);
+                    moved = true;
+                }
+            }
+            if moved {
+                env.storage()
+                    .instance()
+                    .set::<MarketKey, Vec<PendingRelease>>(
+                        &MarketKey::PendingRoyaltyRelease(id),
+                        &pending,
+                    );
+            }
+        }
+        env.storage()
+            .instance()
+            .remove(&MarketKey::CreatorTokens(old.clone()));
+        env.storage()
+            .instance()
+            .set::<DataKey, Vec<Address>>(&DataKey::RedirectSources(new.clone()), &sources);
+
+        env.events().publish((symbol_short!("creatorsh"), old), new);
+        Ok(())
+    }
+
+    // Catalogue của creator, kể cả token collab mà creator là co-creator
+    pub fn get_creator_tokens(env: Env, creator: Address) -> Vec<u128> {
+        creator_tokens(&env, &creator)
+    }
+
+    pub fn get_creator_redirect(env: Env, creator: Address) -> Option<Address> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::CreatorRedirect(creator))
+    }
+
+    // Ghi lại entry Creator/CoCreator của các token id trong [start, start + limit)
+    // đang trỏ tới địa chỉ cũ của `new`. Ai cũng có thể gọi, trả về số entry đã ghi.
+    pub fn materialize_creatorship(env: Env, new: Address, start: u128, limit: u32) -> u32 {
+        let last: u128 = env
+            .storage()
+            .instance()
+            .get::<DataKey, u128>(&DataKey::NextId)
+            .unwrap_or(0);
+        let first = start.max(1);
+        let end = first
+            .saturating_add(limit as u128)
+            .min(last.saturating_add(1));
+
+        let mut rewritten: u32 = 0;
+        let mut id = first;
+        while id < end {
+            if let Some(raw) = env
+                .storage()
+                .instance()
+                .get::<DataKey, Address>(&DataKey::Creator(id))
+            {
+                if raw != new && resolve_creator(&env, raw) == new {
+                    set_creator(&env, id, &new);
+                    rewritten += 1;
+                }
+            }
+            if let Some(mut co) = env
+                .storage()
+                .instance()
+                .get::<DataKey, CoCreatorInfo>(&DataKey::CoCreator(id))
+            {
+                if co.co_creator != new && resolve_creator(&env, co.co_creator.clone()) == new {
+                    co.co_creator = new.clone();
+                    set_co_creator(&env, id, &co);
+                    rewritten += 1;
+                }
+            }
+            id += 1;
+        }
+        rewritten
+    }
+
+    // NFT thành viên không chuyển nhượng được (khoá vĩnh viễn), royalty 0
+    pub fn mint_membership(
+        env: Env,
+        admin: Address,
+        recipient: Address,
+        tier_level: u32,
+        benefits: Benefits,
+        expires_at: u64,
+        uri: Bytes,
+    ) -> Result<TokenId, Error> {
+        require_admin(&env, &admin)?;
+        if uri.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        ensure_not_this_contract(&env, &recipient)?;
+        // Giảm 100% sẽ cho mua giá 0, giống giới hạn của promo
+        if benefits.discount_bps >= 10_000 {
+            return Err(Error::BpsOutOfRange);
+        }
+        if expires_at <= env.ledger().timestamp() {
+            return Err(Error::InvalidWindow);
+        }
+
+        let id = mint_token(&env, &admin, &recipient, 0, &uri)?;
+        set_locked(&env, id, true);
+        env.storage().instance().set::<DataKey, Membership>(
+            &DataKey::MembershipBenefits(id),
+            &Membership {
+                tier_level,
+                benefits,
+                expires_at,
+            },
+        );
+        let mut tokens = member_tokens(&env, &recipient);
+        tokens.push_back(id);
+        env.storage()
+            .instance()
+            .set::<MarketKey, Vec<u128>>(&MarketKey::MemberTokens(recipient.clone()), &tokens);
+
+        env.events().publish(
+            (symbol_short!("member"), id),
+            (recipient, tier_level, expires_at),
+        );
+        log_admin_action(&env, symbol_short!("member"), &admin, LogSubject::Token(id));
+        Ok(TokenId(id))
+    }
+
+    pub fn get_membership(env: Env, token_id: TokenId) -> Option<Membership> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Membership>(&DataKey::MembershipBenefits(token_id.0))
+    }
+
+    pub fn get_membership_benefits(env: Env, member: Address) -> Option<Benefits> {
+        membership_benefits(&env, &member)
+    }
+
+    pub fn set_minting_windows(
+        env: Env,
+        creator: Address,
+        windows: Vec<MintWindow>,
+    ) -> Result<(), Error> {
+        creator.require_auth();
+        for w in windows.iter() {
+            if w.start > w.end {
+                return Err(Error::InvalidWindow);
+            }
+        }
+        env.storage()
+            .instance()
+            .set::<DataKey, Vec<MintWindow>>(&DataKey::MintingWindow(creator), &windows);
+        Ok(())
+    }
+
+    pub fn get_minting_windows(env: Env, creator: Address) -> Vec<MintWindow> {
+        env.storage()
+            .instance()
+            .get::<DataKey, Vec<MintWindow>>(&DataKey::MintingWindow(creator))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    pub fn set_window_exempt(
+        env: Env,
+        admin: Address,
+        creator: Address,
+        exempt: bool,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<DataKey, bool>(&DataKey::WindowExempt(creator.clone()), &exempt);
+        log_admin_action(
+            &env,
+            symbol_short!("win_exmpt"),
+            &admin,
+            LogSubject::Address(creator),
+        );
+        Ok(())
+    }
+
+    pub fn mint_collab(
+        env: Env,
+        creator_a: Address,
+        creator_b: Address,
+        initial_owner: Address,
+        royalty_bps: u32,
+        uri: Bytes,
+        split_a_bps: u32,
+    ) -> Result<TokenId, Error> {
+        creator_a.require_auth();
+        creator_b.require_auth();
+        if creator_a == creator_b || split_a_bps > 10_000 {
+            return Err(Error::InvalidSplit);
+        }
+        if uri.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        check_mint_window(&env, &creator_a)?;
+        check_mint_window(&env, &creator_b)?;
+
+        let id = mint_token(&env, &creator_a, &initial_owner, royalty_bps, &uri)?;
+        index_creator_token(&env, &creator_b, id);
+        set_co_creator(
+            &env,
+            id,
+            &CoCreatorInfo {
+                co_creator: creator_b,
+                split_a_bps,
+            },
+        );
+        Ok(TokenId(id))
+    }
+
+    pub fn get_info(env: Env, token_id: TokenId) -> Result<NftInfo, Error> {
+        let id = token_id.0;
+        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+        let creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
+        let royalty_bps = get_royalty_bps(&env, id).ok_or(Error::TokenNotFound)?;
+        let uri = get_uri(&env, id).ok_or(Error::TokenNotFound)?;
+        let minted_at = mint_timestamp(&env, id).unwrap_or(0);
+
+        Ok(NftInfo {
+            token_id,
+            owner,
+            creator,
+            royalty_bps,
+            uri,
+            minted_at,
+        })
+    }
+
+    pub fn get_provenance_hash(env: Env, token_id: TokenId) -> Option<BytesN<32>> {
+        env.storage()
+            .instance()
+            .get::<DataKey, BytesN<32>>(&DataKey::ProvenanceHash(token_id.0))
+    }
+
+    // Tính lại hash từ dữ liệu mint đang lưu (owner lúc mint, không phải owner hiện tại)
+    pub fn verify_provenance(env: Env, token_id: TokenId) -> bool {
+        let id = token_id.0;
+        let stored = match env
+            .storage()
+            .instance()
+            .get::<DataKey, BytesN<32>>(&DataKey::ProvenanceHash(id))
+        {
+            Some(h) => h,
+            None => return false,
+        };
+        let minted_to = env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::MintedTo(id));
+        let raw_creator = env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::Creator(id));
+        match (
+            raw_creator,
+            minted_to,
+            get_royalty_bps(&env, id),
+            get_uri(&env, id),
+            mint_timestamp(&env, id),
+        ) {
+            (Some(creator), Some(owner), Some(bps), Some(uri), Some(minted_at)) => {
+                // Sau materialize_creatorship, creator lúc mint là một trong các địa chỉ cũ
+                let mut candidates = redirect_sources(&env, &creator);
+                candidates.push_front(creator);
+                candidates
+                    .iter()
+                    .any(|c| provenance_hash(&env, &c, &owner, bps, &uri, minted_at) == stored)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get_mint_timestamp(env: Env, token_id: TokenId) -> Option<u64> {
+        mint_timestamp(&env, token_id.0)
+    }
+
+    pub fn get_token_age_seconds(env: Env, token_id: TokenId) -> Option<u64> {
+        let minted_at = mint_timestamp(&env, token_id.0)?;
+        Some(env.ledger().timestamp().saturating_sub(minted_at))
+    }
+
+    pub fn get_info_ext(env: Env, token_id: TokenId) -> Result<ExtendedNftInfo, Error> {
+        let id = token_id.0;
+        let info = Self::get_info(env.clone(), token_id)?;
+        let (co_creator, split_a_bps) = co_creator_split(&env, id);
+
+        Ok(ExtendedNftInfo {
+            info,
+            co_creator,
+            split_a_bps,
+            backup_uri: backup_uri(&env, id),
+            external_links: external_links(&env, id),
+        })
+    }
+
+    pub fn set_link_platforms(
+        env: Env,
+        admin: Address,
+        platforms: Vec<Bytes>,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<DataKey, Vec<Bytes>>(&DataKey::LinkPlatforms, &platforms);
+        log_admin_action(&env, symbol_short!("platforms"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    pub fn get_link_platforms(env: Env) -> Vec<Bytes> {
+        link_platforms(&env)
+    }
+
+    pub fn set_external_links(
+        env: Env,
+        creator: Address,
+        token_id: TokenId,
+        links: Vec<ExternalLink>,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_creator(&env, id, &creator)?;
+        ensure_not_frozen(&env, id)?;
+        if links.len() > MAX_EXTERNAL_LINKS {
+            return Err(Error::CapExceeded);
+        }
+        let platforms = link_platforms(&env);
+        for link in links.iter() {
+            if link.url.is_empty() || link.url.len() > MAX_LINK_URL_LEN {
+                return Err(Error::InvalidLink);
+            }
+            if !platforms.contains(&link.platform) {
+                return Err(Error::InvalidLink);
+            }
+        }
+        env.storage()
+            .instance()
+            .set::<DataKey, Vec<ExternalLink>>(&DataKey::ExternalLinks(id), &links);
+        Ok(())
+    }
+
+    pub fn get_external_links(env: Env, token_id: TokenId) -> Vec<ExternalLink> {
+        external_links(&env, token_id.0)
+    }
+
+    pub fn set_allow_backup(env: Env, creator: Address, allowed: bool) {
+        creator.require_auth();
+        env.storage()
+            .instance()
+            .set::<DataKey, bool>(&DataKey::AllowBackup(creator), &allowed);
+    }
+
+    // Admin ghim URI dự phòng cho token; backup rỗng = xoá. Không thay URI gốc.
+    pub fn set_backup_uri(
+        env: Env,
+        admin: Address,
+        token_id: TokenId,
+        backup: Bytes,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        let id = token_id.0;
+        let creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
+        if !allows_backup(&env, &creator) {
+            return Err(Error::NotAuthorized);
+        }
+        if let Some(co) = get_co_creator(&env, id) {
+            if !allows_backup(&env, &co.co_creator) {
+                return Err(Error::NotAuthorized);
+            }
+        }
+
+        if backup.is_empty() {
+            env.storage().instance().remove(&DataKey::BackupUri(id));
+            env.events().publish((symbol_short!("bkp_clear"), id), ());
+        } else {
+            env.storage()
+                .instance()
+                .set::<DataKey, Bytes>(&DataKey::BackupUri(id), &backup);
+            env.events().publish((symbol_short!("bkp_set"), id), backup);
+        }
+        log_admin_action(&env, symbol_short!("backup"), &admin, LogSubject::Token(id));
+        Ok(())
+    }
+
+    // Khoá metadata sau khi reveal, không thể mở lại
+    pub fn freeze_metadata(env: Env, creator: Address, token_id: TokenId) -> Result<(), Error> {
+        let id = token_id.0;
+        require_creator(&env, id, &creator)?;
+        ensure_not_frozen(&env, id)?;
+        env.storage()
+            .instance()
+            .set::<DataKey, bool>(&DataKey::MetadataFrozen(id), &true);
+        env.events()
+            .publish((symbol_short!("md_frozen"), id), creator);
+        Ok(())
+    }
+
+    pub fn is_metadata_frozen(env: Env, token_id: TokenId) -> bool {
+        metadata_frozen(&env, token_id.0)
+    }
+
+    // uri rỗng = xoá bản ngôn ngữ đó
+    pub fn set_uri_variant(
+        env: Env,
+        creator: Address,
+        token_id: TokenId,
+        lang: Symbol,
+        uri: Bytes,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_creator(&env, id, &creator)?;
+        ensure_not_frozen(&env, id)?;
+
+        let key = DataKey::UriVariant(id, lang.clone());
+        let mut langs = uri_langs(&env, id);
+        if uri.is_empty() {
+            env.storage().instance().remove(&key);
+            if let Some(i) = langs.first_index_of(&lang) {
+                langs.remove(i);
+            }
+        } else {
+            if !langs.contains(&lang) {
+                if langs.len() >= MAX_URI_VARIANTS {
+                    return Err(Error::CapExceeded);
+                }
+                langs.push_back(lang);
+            }
+            env.storage().instance().set::<DataKey, Bytes>(&key, &uri);
+        }
+        env.storage()
+            .instance()
+            .set::<DataKey, Vec<Symbol>>(&DataKey::UriLangs(id), &langs);
+        Ok(())
+    }
+
+    // Trả về bản URI theo ngôn ngữ, nếu không có thì dùng URI gốc
+    pub fn get_uri_lang(env: Env, token_id: TokenId, lang: Symbol) -> Option<Bytes> {
+        let id = token_id.0;
+        env.storage()
+            .instance()
+            .get::<DataKey, Bytes>(&DataKey::UriVariant(id, lang))
+            .or_else(|| get_uri(&env, id))
+    }
+
+    pub fn get_uri_langs(env: Env, token_id: TokenId) -> Vec<Symbol> {
+        uri_langs(&env, token_id.0)
+    }
+
+    pub fn get_backup_uri(env: Env, token_id: TokenId) -> Option<Bytes> {
+        backup_uri(&env, token_id.0)
+    }
+
+    pub fn set_trusted_source(env: Env, admin: Address, source: Address) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<DataKey, Address>(&DataKey::TrustedSource, &source);
+        log_admin_action(
+            &env,
+            symbol_short!("trusted"),
+            &admin,
+            LogSubject::Address(source),
+        );
+        Ok(())
+    }
+
+    pub fn export_token(
+        env: Env,
+        owner: Address,
+        token_id: TokenId,
+        destination_contract: Address,
+    ) -> Result<ExportTicket, Error> {
+        let id = token_id.0;
+        let current = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+        if current != owner {
+            return Err(Error::NotOwner);
+        }
+        owner.require_auth();
+        ensure_unlocked(&env, id)?;
+
+        let (co_creator, split_a_bps) = co_creator_split(&env, id);
+        let mut ticket = ExportTicket {
+            source_contract: env.current_contract_address(),
+            destination_contract,
+            token_id: id,
+            owner,
+            creator: get_creator(&env, id).ok_or(Error::TokenNotFound)?,
+            co_creator,
+            split_a_bps,
+            royalty_bps: get_royalty_bps(&env, id).ok_or(Error::TokenNotFound)?,
+            uri: get_uri(&env, id).ok_or(Error::TokenNotFound)?,
+            uri_variants: uri_variants(&env, id),
+            metadata_frozen: metadata_frozen(&env, id),
+            retired: retired(&env, id),
+            commitment: BytesN::from_array(&env, &[0u8; 32]),
+        };
+        ticket.commitment = export_commitment(&env, &ticket);
+
+        // Tombstone: xoá owner để token không thể transfer/buy/export lại
+        env.storage().instance().remove(&DataKey::Owner(id));
+        env.storage()
+            .instance()
+            .set::<DataKey, BytesN<32>>(&DataKey::Exported(id), &ticket.commitment);
+
+        env.events().publish(
+            (symbol_short!("export"), ticket.destination_contract.clone()),
+            (id, ticket.commitment.clone()),
+        );
+        Ok(ticket)
+    }
+
+    pub fn get_export_commitment(env: Env, token_id: TokenId) -> Option<BytesN<32>> {
+        env.storage()
+            .instance()
+            .get::<DataKey, BytesN<32>>(&DataKey::Exported(token_id.0))
+    }
+
+    pub fn import_token(env: Env, ticket: ExportTicket, owner: Address) -> Result<TokenId, Error> {
+        owner.require_auth();
+        if ticket.owner != owner {
+            return Err(Error::NotOwner);
+        }
+        let trusted = env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::TrustedSource)
+            .ok_or(Error::UntrustedSource)?;
+        if ticket.source_contract != trusted {
+            return Err(Error::UntrustedSource);
+        }
+        if ticket.destination_contract != env.current_contract_address()
+            || export_commitment(&env, &ticket) != ticket.commitment
+        {
+            return Err(Error::InvalidTicket);
+        }
+        let imported_key = DataKey::Imported(ticket.source_contract.clone(), ticket.token_id);
+        if env.storage().instance().has(&imported_key) {
+            return Err(Error::AlreadyImported);
+        }
+
+        // Xác nhận với contract nguồn rằng token thật sự đã được export với commitment này
+        let mut args: Vec<Val> = Vec::new(&env);
+        args.push_back(TokenId(ticket.token_id).into_val(&env));
+        let recorded = env.invoke_contract::<Option<BytesN<32>>>(
+            &ticket.source_contract,
+            &Symbol::new(&env, "get_export_commitment"),
+            args,
+        );
+        if recorded != Some(ticket.commitment.clone()) {
+            return Err(Error::InvalidTicket);
+        }
+
+        let id = mint_token(
+            &env,
+            &ticket.creator,
+            &owner,
+            ticket.royalty_bps,
+            &ticket.uri,
+        )?;
+        if let Some(co_creator) = ticket.co_creator.clone() {
+            index_creator_token(&env, &co_creator, id);
+            set_co_creator(
+                &env,
+                id,
+                &CoCreatorInfo {
+                    co_creator,
+                    split_a_bps: ticket.split_a_bps,
+                },
+            );
+        }
+        let mut langs: Vec<Symbol> = Vec::new(&env);
+        for (lang, uri) in ticket.uri_variants.iter() {
+            env.storage()
+                .instance()
+                .set::<DataKey, Bytes>(&DataKey::UriVariant(id, lang.clone()), &uri);
+            langs.push_back(lang);
+        }
+        if !langs.is_empty() {
+            env.storage()
+                .instance()
+                .set::<DataKey, Vec<Symbol>>(&DataKey::UriLangs(id), &langs);
+        }
+        if ticket.metadata_frozen {
+            env.storage()
+                .instance()
+                .set::<DataKey, bool>(&DataKey::MetadataFrozen(id), &true);
+        }
+        if ticket.retired {
+            env.storage()
+                .instance()
+                .set::<MarketKey, bool>(&MarketKey::Retired(id), &true);
+        }
+        env.storage()
+            .instance()
+            .set::<DataKey, u128>(&imported_key, &id);
+
+        env.events().publish(
+            (symbol_short!("import"), ticket.source_contract),
+            (ticket.token_id, id),
+        );
+        Ok(TokenId(id))
+    }
+
+    pub fn transfer(env: Env, token_id: TokenId, from: Address, to: Address) -> Result<(), Error> {
+        let id = token_id.0;
+        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+        if owner != from {
+            return Err(Error::NotOwner);
+        }
+        from.require_auth();
+        check_acl(&env, id, &from)?;
+        ensure_unlocked(&env, id)?;
+        if from == to {
+            return Err(Error::SameOwner);
+        }
+        ensure_not_this_contract(&env, &to)?;
+        set_owner(&env, id, &to);
+        Ok(())
+    }
+
+    pub fn set_points_transferable(
+        env: Env,
+        admin: Address,
+        transferable: bool,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<FanKey, bool>(&FanKey::PointsTransferable, &transferable);
+        log_admin_action(&env, symbol_short!("pt_xfer"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    // Tặng token kèm điểm của người gửi trong một lần gọi; lỗi ở bất kỳ phần
+    // nào thì cả giao dịch bị huỷ, token không đổi chủ.
+    pub fn gift_with_points(
+        env: Env,
+        from: Address,
+        token_id: TokenId,
+        to: Address,
+        points: u128,
+        memo: Bytes,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+        if owner != from {
+            return Err(Error::NotOwner);
+        }
+        from.require_auth();
+        check_acl(&env, id, &from)?;
+        ensure_unlocked(&env, id)?;
+        if from == to {
+            return Err(Error::SameOwner);
+        }
+        ensure_not_this_contract(&env, &to)?;
+        if memo.len() > MAX_GIFT_MEMO_LEN {
+            return Err(Error::CapExceeded);
+        }
+
+        if points > 0 {
+            let transferable = env
+                .storage()
+                .instance()
+                .get::<FanKey, bool>(&FanKey::PointsTransferable)
+                .unwrap_or(false);
+            if !transferable {
+                return Err(Error::PointsNotTransferable);
+            }
+            // Không để giới hạn mỗi địa chỉ cắt mất điểm của người gửi
+            let max_points = fan_point_setting(&env, FanKey::MaxFanPointsPerAddress);
+            let received = env
+                .storage()
+                .instance()
+                .get::<DataKey, u128>(&fan_key(&to))
+                .unwrap_or(0u128)
+                .checked_add(points)
+                .ok_or(Error::Overflow)?;
+            if max_points > 0 && received > max_points {
+                return Err(Error::CapExceeded);
+            }
+            spend_fan_points(&env, &from, points)?;
+            add_fan_points(&env, &to, points)?;
+        }
+
+        set_owner(&env, id, &to);
+        env.events()
+            .publish((symbol_short!("gift"), id), (from, to, points, memo));
+        Ok(())
+    }
+
+    pub fn register_signing_key(env: Env, owner: Address, public_key: BytesN<32>) {
+        owner.require_auth();
+        env.storage()
+            .instance()
+            .set::<MarketKey, BytesN<32>>(&MarketKey::SigningKey(owner), &public_key);
+    }
+
+    pub fn get_signing_key(env: Env, owner: Address) -> Option<BytesN<32>> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, BytesN<32>>(&MarketKey::SigningKey(owner))
+    }
+
+    // Relayer nộp thay owner. Owner ký bằng khoá đã đăng ký lên
+    // token_id || xdr(from) || xdr(to) || nonce || deadline || xdr(contract),
+    // số nguyên big-endian.
+    pub fn execute_signed_transfer(
+        env: Env,
+        token_id: TokenId,
+        from: Address,
+        to: Address,
+        nonce: u128,
+        deadline: u64,
+        signature: Bytes,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        let public_key = env
+            .storage()
+            .instance()
+            .get::<MarketKey, BytesN<32>>(&MarketKey::SigningKey(from.clone()))
+            .ok_or(Error::NotAuthorized)?;
+        if env.ledger().timestamp() > deadline {
+            return Err(Error::NotActive);
+        }
+        let nonce_key = DataKey::UsedTransferNonce(from.clone(), nonce);
+        if env.storage().persistent().has(&nonce_key) {
+            return Err(Error::NonceUsed);
+        }
+        let signature: BytesN<64> = signature.try_into().map_err(|_| Error::InvalidSignature)?;
+
+        let mut message = Bytes::from_array(&env, &id.to_be_bytes());
+        message.append(&from.clone().to_xdr(&env));
+        message.append(&to.clone().to_xdr(&env));
+        message.extend_from_array(&nonce.to_be_bytes());
+        message.extend_from_array(&deadline.to_be_bytes());
+        message.append(&env.current_contract_address().to_xdr(&env));
+        // ed25519_verify panic nếu chữ ký sai
+        env.crypto()
+            .ed25519_verify(&public_key, &message, &signature);
+
+        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+        if owner != from {
+            return Err(Error::NotOwner);
+        }
+        check_acl(&env, id, &from)?;
+        ensure_unlocked(&env, id)?;
+        if from == to {
+            return Err(Error::SameOwner);
+        }
+        ensure_not_this_contract(&env, &to)?;
+
+        env.storage()
+            .persistent()
+            .set::<DataKey, bool>(&nonce_key, &true);
+        // Sau deadline chữ ký tự hết hiệu lực
+        let window = (deadline - env.ledger().timestamp()) / SECONDS_PER_LEDGER;
+        keep_replay_key(&env, &nonce_key, window.min(u32::MAX as u64) as u32);
+        set_owner(&env, id, &to);
+        Ok(())
+    }
+
+    pub fn transfer_from(
+        env: Env,
+        spender: Address,
+        token_id: TokenId,
+        from: Address,
+        to: Address,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+        if owner != from {
+            return Err(Error::NotOwner);
+        }
+        spender.require_auth();
+        if spender != from && !is_operator(&env, &from, &spender) {
+            return Err(Error::NotAuthorized);
+        }
+        check_acl(&env, id, &spender)?;
+        ensure_unlocked(&env, id)?;
+        if from == to {
+            return Err(Error::SameOwner);
+        }
+        ensure_not_this_contract(&env, &to)?;
+        set_owner(&env, id, &to);
+        Ok(())
+    }
+
+    // Chuyển cưỡng chế (lệnh toà, tài khoản bị hack...). Admin chỉ nộp yêu cầu:
+    // lệnh chạy qua hội đồng như mọi GovAction (đủ chữ ký, qua timelock) rồi
+    // execute_gov_action mới chuyển token. Trả về id đề xuất.
+    pub fn emergency_transfer(
+        env: Env,
+        admin: Address,
+        token_id: TokenId,
+        to: Address,
+        reason: Bytes,
+    ) -> Result<u64, Error> {
+        require_admin(&env, &admin)?;
+        if reason.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        let action = GovAction::EmergencyTransfer(token_id.0, to, reason);
+        validate_gov_action(&env, &action)?;
+        store_gov_proposal(&env, &admin, action, Vec::new(&env))
+    }
+
+    pub fn get_emergency_transfers(env: Env) -> Vec<EmergencyRecord> {
+        emergency_records(&env)
+    }
+
+    pub fn set_approval_for_all(
+        env: Env,
+        owner: Address,
+        operator: Address,
+        approved: bool,
+    ) -> Result<(), Error> {
+        owner.require_auth();
+        if owner == operator {
+            return Err(Error::SelfAddress);
+        }
+        write_operator_approval(&env, &owner, &operator, approved, u32::MAX);
+        if approved {
+            // Cảnh báo cho ví: approval không có hạn
+            env.events()
+                .publish((symbol_short!("unbounded"), owner, operator), ());
+        }
+        Ok(())
+    }
+
+    pub fn set_approval_for_all_until(
+        env: Env,
+        owner: Address,
+        operator: Address,
+        approved: bool,
+        until_ledger: u32,
+    ) -> Result<(), Error> {
+        owner.require_auth();
+        if owner == operator {
+            return Err(Error::SelfAddress);
+        }
+        write_operator_approval(&env, &owner, &operator, approved, until_ledger);
+        Ok(())
+    }
+
+    pub fn is_approved_for_all(env: Env, owner: Address, operator: Address) -> bool {
+        is_operator(&env, &owner, &operator)
+    }
+
+    // Marketplace đổi địa chỉ: trong thời hạn, approval cho old_operator cũng
+    // có hiệu lực với new_operator (một bước, không nối chuỗi).
+    pub fn register_operator_successor(
+        env: Env,
+        admin: Address,
+        old_operator: Address,
+        new_operator: Address,
+        until_ledger: u32,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        if old_operator == new_operator || until_ledger < env.ledger().sequence() {
+            return Err(Error::InvalidRedirect);
+        }
+        // Ghi lại danh sách đã lọc nên entry hết hạn bị xoá luôn
+        let mut successors = active_operator_successors(&env);
+        for s in successors.iter() {
+            if s.old_operator == old_operator
+                || s.old_operator == new_operator
+                || s.new_operator == old_operator
+            {
+                return Err(Error::InvalidRedirect);
+            }
+        }
+        successors.push_back(OperatorSuccessor {
+            old_operator: old_operator.clone(),
+            new_operator: new_operator.clone(),
+            until_ledger,
+        });
+        env.storage()
+            .instance()
+            .set::<MarketKey, Vec<OperatorSuccessor>>(&MarketKey::OperatorSuccessors, &successors);
+
+        env.events().publish(
+            (symbol_short!("op_succ"), old_operator),
+            (new_operator, until_ledger),
+        );
+        log_admin_action(&env, symbol_short!("op_succ"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    pub fn get_operator_successors(env: Env) -> Vec<OperatorSuccessor> {
+        active_operator_successors(&env)
+    }
+
+    // Ai cũng gọi được: xoá các mapping đã hết hạn, trả về số entry đã xoá
+    pub fn prune_operator_successors(env: Env) -> u32 {
+        let stored = env
+            .storage()
+            .instance()
+            .get::<MarketKey, Vec<OperatorSuccessor>>(&MarketKey::OperatorSuccessors)
+            .map(|v| v.len())
+            .unwrap_or(0);
+        let active = active_operator_successors(&env);
+        let pruned = stored - active.len();
+        if active.is_empty() {
+            env.storage()
+                .instance()
+                .remove(&MarketKey::OperatorSuccessors);
+        } else if pruned > 0 {
+            env.storage()
+                .instance()
+                .set::<MarketKey, Vec<OperatorSuccessor>>(&MarketKey::OperatorSuccessors, &active);
+        }
+        pruned
+    }
+
+    // Owner chuyển hẳn approval của mình từ old sang new, giữ nguyên hạn cũ
+    pub fn confirm_operator_migration(
+        env: Env,
+        owner: Address,
+        old_operator: Address,
+        new_operator: Address,
+    ) -> Result<(), Error> {
+        owner.require_auth();
+        let registered = active_operator_successors(&env)
+            .iter()
+            .any(|s| s.old_operator == old_operator && s.new_operator == new_operator);
+        if !registered {
+            return Err(Error::NotActive);
+        }
+        let until_ledger = env
+            .storage()
+            .instance()
+            .get::<DataKey, u32>(&DataKey::OperatorApproval(
+                owner.clone(),
+                old_operator.clone(),
+            ))
+            .ok_or(Error::NotAuthorized)?;
+        if env.ledger().sequence() > until_ledger {
+            return Err(Error::NotAuthorized);
+        }
+
+        write_operator_approval(&env, &owner, &old_operator, false, 0);
+        write_operator_approval(&env, &owner, &new_operator, true, until_ledger);
+        env.events().publish(
+            (symbol_short!("op_migr"), owner),
+            (old_operator, new_operator),
+        );
+        Ok(())
+    }
+
+    pub fn set_token_acl(
+        env: Env,
+        owner: Address,
+        token_id: TokenId,
+        allowed: Vec<Address>,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_owner(&env, id, &owner)?;
+        env.storage()
+            .instance()
+            .set::<DataKey, Vec<Address>>(&DataKey::TokenAcl(id), &allowed);
+        env.storage()
+            .instance()
+            .set::<DataKey, bool>(&DataKey::TokenAclEnabled(id), &enabled);
+        Ok(())
+    }
+
+    pub fn add_to_token_acl(
+        env: Env,
+        owner: Address,
+        token_id: TokenId,
+        address: Address,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_owner(&env, id, &owner)?;
+        let mut acl = token_acl(&env, id);
+        if !acl.contains(&address) {
+            acl.push_back(address);
+            env.storage()
+                .instance()
+                .set::<DataKey, Vec<Address>>(&DataKey::TokenAcl(id), &acl);
+        }
+        Ok(())
+    }
+
+    pub fn remove_from_token_acl(
+        env: Env,
+        owner: Address,
+        token_id: TokenId,
+        address: Address,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_owner(&env, id, &owner)?;
+        let mut acl = token_acl(&env, id);
+        if let Some(i) = acl.first_index_of(&address) {
+            acl.remove(i);
+            env.storage()
+                .instance()
+                .set::<DataKey, Vec<Address>>(&DataKey::TokenAcl(id), &acl);
+        }
+        Ok(())
+    }
+
+    pub fn get_token_acl(env: Env, token_id: TokenId) -> (Vec<Address>, bool) {
+        let id = token_id.0;
+        let enabled = env
+            .storage()
+            .instance()
+            .get::<DataKey, bool>(&DataKey::TokenAclEnabled(id))
+            .unwrap_or(false);
+        (token_acl(&env, id), enabled)
+    }
+
+    pub fn get_fan_points(env: Env, fan: Address) -> u128 {
+        env.storage()
+            .instance()
+            .get::<DataKey, u128>(&DataKey::FanPoints(fan))
+            .unwrap_or(0u128)
+    }
+
+    // Backend gửi lại cùng idempotency_key khi retry: nếu lần trước đã thành công
+    // thì trả DuplicateGrant và không cộng điểm lần nữa. Lần trước lỗi thì key
+    // chưa được ghi (giao dịch bị huỷ) nên retry được xử lý bình thường.
+    pub fn award_fan_points(
+        env: Env,
+        granter: Address,
+        fan: Address,
+        points: u128,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> Result<(), Error> {
+        granter.require_auth();
+        if points == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        if let Some(key) = &idempotency_key {
+            if grant_key_used(&env, key) {
+                return Err(Error::DuplicateGrant);
+            }
+        }
+        let bonus = endorsement_bonus(&env, &fan, points)?;
+        let total = points.checked_add(bonus).ok_or(Error::Overflow)?;
+        add_fan_points(&env, &fan, total)?;
+        if let Some(key) = idempotency_key {
+            let storage_key = FanKey::GrantKey(key);
+            env.storage()
+                .persistent()
+                .set::<FanKey, u32>(&storage_key, &env.ledger().sequence());
+            let retention = grant_key_retention(&env);
+            let ledgers = if retention == 0 {
+                env.storage().max_ttl()
+            } else {
+                retention
+            };
+            keep_replay_key(&env, &storage_key, ledgers);
+        }
+        Ok(())
+    }
+
+    pub fn set_granter(
+        env: Env,
+        admin: Address,
+        granter: Address,
+        allowed: bool,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        let mut list = granters(&env);
+        match list.first_index_of(&granter) {
+            Some(i) if !allowed => {
+                list.remove(i);
+            }
+            None if allowed => list.push_back(granter.clone()),
+            _ => {}
+        }
+        env.storage()
+            .instance()
+            .set::<FanKey, Vec<Address>>(&FanKey::Granters, &list);
+        log_admin_action(
+            &env,
+            symbol_short!("granter"),
+            &admin,
+            LogSubject::Address(granter),
+        );
+        Ok(())
+    }
+
+    pub fn get_granters(env: Env) -> Vec<Address> {
+        granters(&env)
+    }
+
+    // Granter nộp voucher fan đã ký (bằng khoá đăng ký qua register_signing_key)
+    // lên xdr(voucher) || xdr(contract). Chữ ký sai thì ed25519_verify panic.
+    pub fn redeem_signed(
+        env: Env,
+        relayer: Address,
+        voucher: SpendVoucher,
+        signature: BytesN<64>,
+    ) -> Result<(), Error> {
+        relayer.require_auth();
+        if !granters(&env).contains(&relayer) {
+            return Err(Error::NotAuthorized);
+        }
+        let public_key = env
+            .storage()
+            .instance()
+            .get::<MarketKey, BytesN<32>>(&MarketKey::SigningKey(voucher.fan.clone()))
+            .ok_or(Error::SigningKeyNotRegistered)?;
+        if env.ledger().sequence() > voucher.expiry_ledger {
+            return Err(Error::VoucherExpired);
+        }
+        let nonce_key = FanKey::UsedSpendNonce(voucher.fan.clone(), voucher.nonce);
+        if env.storage().persistent().has(&nonce_key) {
+            return Err(Error::NonceUsed);
+        }
+
+        let mut message = voucher.clone().to_xdr(&env);
+        message.append(&env.current_contract_address().to_xdr(&env));
+        env.crypto()
+            .ed25519_verify(&public_key, &message, &signature);
+
+        env.storage()
+            .persistent()
+            .set::<FanKey, bool>(&nonce_key, &true);
+        keep_replay_key(
+            &env,
+            &nonce_key,
+            voucher.expiry_ledger - env.ledger().sequence(),
+        );
+        spend_fan_points(&env, &voucher.fan, voucher.points)?;
+        env.events().publish(
+            (symbol_short!("pt_spend"), voucher.fan),
+            (voucher.points, voucher.purpose, relayer),
+        );
+        Ok(())
+    }
+
+    // Số ledger giữ key idempotency, 0 = giữ mãi
+    pub fn set_grant_key_retention(env: Env, admin: Address, ledgers: u32) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<FanKey, u32>(&FanKey::GrantKeyRetention, &ledgers);
+        log_admin_action(&env, symbol_short!("grant_ret"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    pub fn is_grant_key_used(env: Env, key: BytesN<32>) -> bool {
+        grant_key_used(&env, &key)
+    }
+
+    // Ai cũng có thể gọi; chỉ xoá các key đã quá thời gian giữ
+    pub fn prune_grant_keys(env: Env, keys: Vec<BytesN<32>>) -> u32 {
+        let mut pruned: u32 = 0;
+        for key in keys.iter() {
+            let storage_key = FanKey::GrantKey(key.clone());
+            if env.storage().persistent().has(&storage_key) && !grant_key_used(&env, &key) {
+                env.storage().persistent().remove(&storage_key);
+                pruned += 1;
+            }
+        }
+        pruned
+    }
+
+    // 0 = không giới hạn
+    pub fn set_fan_point_caps(
+        env: Env,
+        admin: Address,
+        per_address: u128,
+        global: u128,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<FanKey, u128>(&FanKey::MaxFanPointsPerAddress, &per_address);
+        env.storage()
+            .instance()
+            .set::<FanKey, u128>(&FanKey::MaxTotalFanPoints, &global);
+        log_admin_action(&env, symbol_short!("pt_caps"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    // (giới hạn mỗi địa chỉ, giới hạn toàn hệ thống, tổng hiện tại)
+    pub fn get_fan_point_caps(env: Env) -> (u128, u128, u128) {
+        (
+            fan_point_setting(&env, FanKey::MaxFanPointsPerAddress),
+            fan_point_setting(&env, FanKey::MaxTotalFanPoints),
+            fan_point_setting(&env, FanKey::TotalFanPoints),
+        )
+    }
+
+    // Admin hoặc bot được cấp quyền "sell_pts"
+    pub fn set_seller_points_rate(env: Env, caller: Address, rate_bps: u32) -> Result<(), Error> {
+        let action = symbol_short!("sell_pts");
+        require_admin_or_bot(&env, &caller, &action, rate_bps as u128)?;
+        if rate_bps > 10_000 {
+            return Err(Error::BpsOutOfRange);
+        }
+        env.storage()
+            .instance()
+            .set::<FanKey, u32>(&FanKey::SellerPointsRate, &rate_bps);
+        log_admin_action(&env, action, &caller, LogSubject::None);
+        Ok(())
+    }
+
+    // Admin hoặc bot được cấp quyền "tip_div"
+    pub fn set_tip_points_divisor(env: Env, caller: Address, divisor: u128) -> Result<(), Error> {
+        let action = symbol_short!("tip_div");
+        require_admin_or_bot(&env, &caller, &action, divisor)?;
+        env.storage()
+            .instance()
+            .set::<FanKey, u128>(&FanKey::TipPointsDivisor, &divisor);
+        log_admin_action(&env, action, &caller, LogSubject::None);
+        Ok(())
+    }
+
+    // Tặng tiền trực tiếp cho creator: không phải giao dịch mua nên không có
+    // royalty hay phí. Được ghi vào phần tips của sao kê kỳ hiện tại.
+    pub fn tip_creator_tokens(
+        env: Env,
+        tipper: Address,
+        creator: Address,
+        amount: i128,
+        payment_token: Address,
+    ) -> Result<(), Error> {
+        tipper.require_auth();
+        if amount == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        if amount < 0 {
+            return Err(Error::InvalidPrice);
+        }
+        if tipper == creator {
+            return Err(Error::SelfAddress);
+        }
+        ensure_not_this_contract(&env, &creator)?;
+        ensure_not_this_contract(&env, &payment_token)?;
+        token_transfer_from(&env, &payment_token, &tipper, &creator, amount)?;
+
+        let key = MarketKey::TipsReceivedInTokens(creator.clone());
+        let received: i128 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, i128>(&key)
+            .unwrap_or(0)
+            .checked_add(amount)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, i128>(&key, &received);
+
+        let period = current_period(&env);
+        let mut st = statement(&env, &creator, period);
+        st.tips = st.tips.checked_add(amount).ok_or(Error::Overflow)?;
+        set_statement(&env, &creator, period, &st);
+
+        let divisor = fan_point_setting(&env, FanKey::TipPointsDivisor);
+        if let Some(points) = (amount as u128).checked_div(divisor) {
+            add_reward_points(&env, &tipper, points)?;
+        }
+
+        env.events().publish(
+            (symbol_short!("token_tip"), creator),
+            (tipper, amount, payment_token),
+        );
+        Ok(())
+    }
+
+    pub fn get_tips_received_in_tokens(env: Env, creator: Address) -> i128 {
+        env.storage()
+            .instance()
+            .get::<MarketKey, i128>(&MarketKey::TipsReceivedInTokens(creator))
+            .unwrap_or(0)
+    }
+
+    // Đốt điểm vĩnh viễn; tổng điểm lưu hành giảm nên giới hạn toàn hệ thống có thêm chỗ
+    pub fn burn_points(
+        env: Env,
+        fan: Address,
+        amount: u128,
+        dedication: Option<Symbol>,
+    ) -> Result<(), Error> {
+        fan.require_auth();
+        if amount == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        spend_fan_points(&env, &fan, amount)?;
+
+        let key = FanKey::BurnedPoints(fan.clone());
+        let burned = fan_point_setting(&env, key.clone())
+            .checked_add(amount)
+            .ok_or(Error::Overflow)?;
+        env.storage().instance().set::<FanKey, u128>(&key, &burned);
+        let total = fan_point_setting(&env, FanKey::TotalBurnedPoints)
+            .checked_add(amount)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<FanKey, u128>(&FanKey::TotalBurnedPoints, &total);
+        update_burn_leaderboard(&env, &fan, burned);
+
+        env.events()
+            .publish((symbol_short!("pt_burn"), fan), (amount, dedication));
+        Ok(())
+    }
+
+    pub fn get_burned_points(env: Env, fan: Address) -> u128 {
+        fan_point_setting(&env, FanKey::BurnedPoints(fan))
+    }
+
+    pub fn get_total_burned_points(env: Env) -> u128 {
+        fan_point_setting(&env, FanKey::TotalBurnedPoints)
+    }
+
+    pub fn get_burn_leaderboard(env: Env) -> Vec<(Address, u128)> {
+        burn_leaderboard(&env)
+    }
+
+    // Admin hoặc bot được cấp quyền "endorse"; giới hạn của bot áp lên
+    // multiplier, còn min_points bot không được đổi
+    pub fn set_endorse_config(
+        env: Env,
+        caller: Address,
+        min_points: u128,
+        multiplier_bps: u32,
+    ) -> Result<(), Error> {
+        let action = symbol_short!("endorse");
+        let by_bot = require_admin_or_bot(&env, &caller, &action, multiplier_bps as u128)?;
+        if multiplier_bps > 10_000 {
+            return Err(Error::BpsOutOfRange);
+        }
+        let current_min: u128 = env
+            .storage()
+            .instance()
+            .get::<DataKey, u128>(&DataKey::EndorseMinPoints)
+            .unwrap_or(0);
+        if by_bot && min_points != current_min {
+            return Err(Error::AutomationLimitExceeded);
+        }
+        env.storage()
+            .instance()
+            .set::<DataKey, u128>(&DataKey::EndorseMinPoints, &min_points);
+        env.storage()
+            .instance()
+            .set::<DataKey, u32>(&DataKey::EndorseMultiplierBps, &multiplier_bps);
+        log_admin_action(&env, action, &caller, LogSubject::None);
+        Ok(())
+    }
+
+    pub fn endorse_fan(env: Env, endorser: Address, endorsee: Address) -> Result<(), Error> {
+        endorser.require_auth();
+        if endorser == endorsee {
+            return Err(Error::SelfAddress);
+        }
+        ensure_not_this_contract(&env, &endorsee)?;
+        let min_points: u128 = env
+            .storage()
+            .instance()
+            .get::<DataKey, u128>(&DataKey::EndorseMinPoints)
+            .unwrap_or(0);
+        if Self::get_fan_points(env.clone(), endorser.clone()) < min_points {
+            return Err(Error::InsufficientPoints);
+        }
+
+        let pair_key = DataKey::Endorsement(endorser.clone(), endorsee.clone());
+        if env.storage().instance().has(&pair_key) {
+            return Err(Error::AlreadyEndorsed);
+        }
+        env.storage()
+            .instance()
+            .set::<DataKey, bool>(&pair_key, &true);
+
+        let count = endorsement_count(&env, &endorsee)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<DataKey, u32>(&DataKey::EndorsementCount(endorsee.clone()), &count);
+
+        env.events()
+            .publish((symbol_short!("endorsed"), endorser, endorsee), count);
+        Ok(())
+    }
+
+    pub fn get_endorsement_count(env: Env, fan: Address) -> u32 {
+        endorsement_count(&env, &fan)
+    }
+
+    pub fn fund_conversion(
+        env: Env,
+        admin: Address,
+        token: Address,
+        amount: i128,
+        rate_points_per_unit: u128,
+        max_per_fan: u128,
+    ) -> Result<u32, Error> {
+        require_admin(&env, &admin)?;
+        if amount == 0 || rate_points_per_unit == 0 || max_per_fan == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        if amount < 0 {
+            return Err(Error::InvalidPrice);
+        }
+        ensure_not_this_contract(&env, &token)?;
+        if env.storage().instance().has(&DataKey::Conversion) {
+            return Err(Error::AlreadyActive);
+        }
+
+        let round: u32 = env
+            .storage()
+            .instance()
+            .get::<DataKey, u32>(&DataKey::ConversionRoundId)
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        token_transfer_from(
+            &env,
+            &token,
+            &admin,
+            &env.current_contract_address(),
+            amount,
+        )?;
+
+        env.storage()
+            .instance()
+            .set::<DataKey, u32>(&DataKey::ConversionRoundId, &round);
+        env.storage().instance().set::<DataKey, ConversionRound>(
+            &DataKey::Conversion,
+            &ConversionRound {
+                round,
+                funder: admin.clone(),
+                token: token.clone(),
+                remaining: amount,
+                rate_points_per_unit,
+                max_per_fan,
+            },
+        );
+        log_admin_action(
+            &env,
+            symbol_short!("conv_fund"),
+            &admin,
+            LogSubject::Address(token),
+        );
+        Ok(round)
+    }
+
+    // Đổi điểm lấy token theo tỉ lệ points / rate. Chỉ đốt đúng số điểm tạo ra
+    // số token nguyên, phần dư được giữ lại cho fan.
+    pub fn convert_points(env: Env, fan: Address, points: u128) -> Result<i128, Error> {
+        fan.require_auth();
+        let mut conv = env
+            .storage()
+            .instance()
+            .get::<DataKey, ConversionRound>(&DataKey::Conversion)
+            .ok_or(Error::NotActive)?;
+
+        let units = points / conv.rate_points_per_unit;
+        if units == 0 {
+            return Err(Error::RoundsToZero);
+        }
+        let payout: i128 = i128::try_from(units).map_err(|_| Error::Overflow)?;
+        if payout > conv.remaining {
+            return Err(Error::BudgetExhausted);
+        }
+        let burned = units
+            .checked_mul(conv.rate_points_per_unit)
+            .ok_or(Error::Overflow)?;
+
+        let converted_key = DataKey::ConvertedPoints(conv.round, fan.clone());
+        let converted: u128 = env
+            .storage()
+            .instance()
+            .get::<DataKey, u128>(&converted_key)
+            .unwrap_or(0);
+        let converted = converted.checked_add(burned).ok_or(Error::Overflow)?;
+        if converted > conv.max_per_fan {
+            return Err(Error::CapExceeded);
+        }
+
+        spend_fan_points(&env, &fan, burned)?;
+        conv.remaining -= payout;
+        env.storage()
+            .instance()
+            .set::<DataKey, u128>(&converted_key, &converted);
+        env.storage()
+            .instance()
+            .set::<DataKey, ConversionRound>(&DataKey::Conversion, &conv);
+        token_transfer(&env, &conv.token, &fan, payout)?;
+
+        env.events()
+            .publish((symbol_short!("convert"), fan), (burned, payout));
+        Ok(payout)
+    }
+
+    pub fn close_conversion(env: Env, admin: Address) -> Result<i128, Error> {
+        let conv = env
+            .storage()
+            .instance()
+            .get::<DataKey, ConversionRound>(&DataKey::Conversion)
+            .ok_or(Error::NotActive)?;
+        require_admin(&env, &admin)?;
+
+        // Phần còn lại trả về người đã nạp, kể cả khi admin đã đổi
+        env.storage().instance().remove(&DataKey::Conversion);
+        token_transfer(&env, &conv.token, &conv.funder, conv.remaining)?;
+        log_admin_action(
+            &env,
+            symbol_short!("conv_end"),
+            &admin,
+            LogSubject::Address(conv.token),
+        );
+        Ok(conv.remaining)
+    }
+
+    // `start` tính từ mục cũ nhất còn giữ lại (tối đa ADMIN_LOG_CAP mục)
+    pub fn get_admin_log(env: Env, start: u32, limit: u32) -> Vec<AdminLogEntry> {
+        let count = admin_log_len(&env);
+        let oldest = count.saturating_sub(ADMIN_LOG_CAP as u64);
+        let retained = (count - oldest) as u32;
+        let mut out: Vec<AdminLogEntry> = Vec::new(&env);
+        let end = start.saturating_add(limit).min(retained);
+        for i in start..end {
+            let slot = ((oldest + i as u64) % ADMIN_LOG_CAP as u64) as u32;
+            if let Some(entry) = env
+                .storage()
+                .persistent()
+                .get::<DataKey, AdminLogEntry>(&DataKey::AdminLog(slot))
+            {
+                out.push_back(entry);
+            }
+        }
+        out
+    }
+
+    // Tổng số thao tác đã ghi, kể cả những mục đã bị đẩy ra khỏi ring
+    pub fn admin_log_count(env: Env) -> u64 {
+        admin_log_len(&env)
+    }
+
+    pub fn get_conversion(env: Env) -> Option<ConversionRound> {
+        env.storage()
+            .instance()
+            .get::<DataKey, ConversionRound>(&DataKey::Conversion)
+    }
+
+    // (số thành viên, tổng điểm hiện có của cả cohort)
+    pub fn get_cohort_stats(env: Env, cohort_week: u32) -> (u32, u128) {
+        (
+            cohort_size(&env, cohort_week),
+            fan_point_setting(&env, FanKey::CohortPoints(cohort_week)),
+        )
+    }
+
+    pub fn get_cohort_members(env: Env, cohort_week: u32, page: u32) -> Vec<Address> {
+        cohort_page(&env, cohort_week, page)
+    }
+
+    pub fn buy(
+        env: Env,
+        token_id: TokenId,
+        buyer: Address,
+        price: i128,
+        payment_token: Option<Address>,
+        form_response_hash: Option<BytesN<32>>,
+    ) -> Result<(), Error> {
+        track_legacy_call(&env, symbol_short!("buy"))?;
+        buyer.require_auth();
+        if price <= 0 {
+            return Err(Error::InvalidPrice);
+        }
+
+        let id = token_id.0;
+        let owner = check_buy_allowed(&env, id, &buyer)?;
+        if form_response_hash.is_none() && form_required(&env, id) {
+            return Err(Error::FormResponseRequired);
+        }
+        let pay_token = resolve_pay_token(&env, payment_token)?;
+        let econ =
+            resolve_sale_economics(&env, id, price, Some(&buyer), &pay_token, SaleContext::Buy)?;
+        settle_sale(&env, id, &pay_token, &buyer, &owner, &econ, false)?;
+        let price = econ.price;
+
+        set_owner(&env, id, &buyer);
+        record_acquisition(&env, id);
+
+        if let Some(response_hash) = form_response_hash.clone() {
+            env.storage().instance().set::<DataKey, FormResponse>(
+                &DataKey::FormResponse(id, buyer.clone()),
+                &FormResponse {
+                    response_hash,
+                    fulfilled: false,
+                },
+            );
+        }
+
+        let points: u128 = if price > 0 { price as u128 } else { 0u128 };
+        add_reward_points(&env, &buyer, points)?;
+        add_reward_points(&env, &owner, seller_points(&env, price)?)?;
+
+        env.events().publish(
+            (symbol_short!("sale"), id),
+            (
+                owner,
+                buyer,
+                price,
+                form_response_hash,
+                econ.dust,
+                econ.waived,
+            ),
+        );
+        Ok(())
+    }
+
+    pub fn propose_cross_bundle(
+        env: Env,
+        initiator: Address,
+        token_ids: Vec<u128>,
+        price: i128,
+        payment_token: Address,
+    ) -> Result<Bytes, Error> {
+        initiator.require_auth();
+        if token_ids.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        if price == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        if price < 0 {
+            return Err(Error::InvalidPrice);
+        }
+        ensure_not_this_contract(&env, &payment_token)?;
+        // Giá chia đều cho từng token; phần mỗi token bằng 0 thì không có royalty
+        if price < token_ids.len() as i128 {
+            return Err(Error::RoundsToZero);
+        }
+
+        let mut required_approvals: Vec<Address> = Vec::new(&env);
+        let mut seen: Vec<u128> = Vec::new(&env);
+        for id in token_ids.iter() {
+            if seen.contains(id) {
+                return Err(Error::DuplicateToken);
+            }
+            seen.push_back(id);
+            let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+            if owner != initiator {
+                return Err(Error::NotOwner);
+            }
+            ensure_unlocked(&env, id)?;
+            let creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
+            if !required_approvals.contains(&creator) {
+                required_approvals.push_back(creator);
+            }
+            if let Some(co) = get_co_creator(&env, id) {
+                if !required_approvals.contains(&co.co_creator) {
+                    required_approvals.push_back(co.co_creator);
+                }
+            }
+        }
+
+        let nonce: u64 = env
+            .storage()
+            .instance()
+            .get::<DataKey, u64>(&DataKey::CrossBundleNonce)
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<DataKey, u64>(&DataKey::CrossBundleNonce, &nonce);
+        let bundle_id = Bytes::from_array(&env, &nonce.to_be_bytes());
+
+        let bundle = CrossBundle {
+            initiator,
+            token_ids,
+            price,
+            payment_token,
+            required_approvals,
+            approvals_received: Vec::new(&env),
+        };
+        env.storage()
+            .instance()
+            .set::<DataKey, CrossBundle>(&DataKey::CrossBundle(bundle_id.clone()), &bundle);
+        Ok(bundle_id)
+    }
+
+    pub fn approve_cross_bundle(env: Env, creator: Address, bundle_id: Bytes) -> Result<(), Error> {
+        creator.require_auth();
+        let key = DataKey::CrossBundle(bundle_id);
+        let mut bundle = env
+            .storage()
+            .instance()
+            .get::<DataKey, CrossBundle>(&key)
+            .ok_or(Error::NotActive)?;
+        if !bundle.required_approvals.contains(&creator) {
+            return Err(Error::NotAuthorized);
+        }
+        if !bundle.approvals_received.contains(&creator) {
+            bundle.approvals_received.push_back(creator);
+            env.storage()
+                .instance()
+                .set::<DataKey, CrossBundle>(&key, &bundle);
+        }
+        Ok(())
+    }
+
+    // Chỉ mua được khi mọi creator đã duyệt. Giá chia đều cho từng token
+    // (token cuối nhận phần dư), royalty tính theo từng token.
+    pub fn buy_cross_bundle(env: Env, buyer: Address, bundle_id: Bytes) -> Result<(), Error> {
+        buyer.require_auth();
+        let key = DataKey::CrossBundle(bundle_id.clone());
+        let bundle = env
+            .storage()
+            .instance()
+            .get::<DataKey, CrossBundle>(&key)
+            .ok_or(Error::NotActive)?;
+        if bundle.approvals_received.len() < bundle.required_approvals.len() {
+            return Err(Error::NotActive);
+        }
+        if bundle.initiator == buyer {
+            return Err(Error::SameOwner);
+        }
+
+        let count = bundle.token_ids.len();
+        let share = bundle.price / count as i128;
+        let mut allocated: i128 = 0;
+        // Tổng thực trả, sau khuyến mãi/giảm giá thành viên của từng token
+        let mut paid: i128 = 0;
+        for (i, id) in bundle.token_ids.iter().enumerate() {
+            let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+            if owner != bundle.initiator {
+                return Err(Error::NotOwner);
+            }
+            check_acl(&env, id, &buyer)?;
+            ensure_unlocked(&env, id)?;
+            ensure_exclusivity_over(&env, id)?;
+
+            let part = if i as u32 + 1 == count {
+                bundle.price - allocated
+            } else {
+                share
+            };
+            allocated += part;
+            let econ = resolve_sale_economics(
+                &env,
+                id,
+                part,
+                Some(&buyer),
+                &bundle.payment_token,
+                SaleContext::Bundle,
+            )?;
+            paid += econ.price;
+            settle_sale(
+                &env,
+                id,
+                &bundle.payment_token,
+                &buyer,
+                &owner,
+                &econ,
+                false,
+            )?;
+            set_owner(&env, id, &buyer);
+            record_acquisition(&env, id);
+        }
+
+        env.storage().instance().remove(&key);
+        add_reward_points(&env, &buyer, paid as u128)?;
+        add_reward_points(&env, &bundle.initiator, seller_points(&env, paid)?)?;
+
+        env.events().publish(
+            (symbol_short!("xbundle"), bundle_id),
+            (bundle.initiator, buyer, paid),
+        );
+        Ok(())
+    }
+
+    pub fn get_cross_bundle(env: Env, bundle_id: Bytes) -> Option<CrossBundle> {
+        env.storage()
+            .instance()
+            .get::<DataKey, CrossBundle>(&DataKey::CrossBundle(bundle_id))
+    }
+
+    // Đổi token A + tiền lấy token B. Token A bị khoá và tiền được giữ trong
+    // contract cho đến khi chủ token B chấp nhận hoặc offerer huỷ.
+    pub fn propose_hybrid_swap(
+        env: Env,
+        offerer: Address,
+        token_a: TokenId,
+        cash: i128,
+        payment_token: Address,
+        token_b: TokenId,
+        expires_at: u64,
+    ) -> Result<Bytes, Error> {
+        offerer.require_auth();
+        let (a, b) = (token_a.0, token_b.0);
+        if cash < 0 {
+            return Err(Error::InvalidPrice);
+        }
+        ensure_not_this_contract(&env, &payment_token)?;
+        if expires_at <= env.ledger().timestamp() {
+            return Err(Error::InvalidWindow);
+        }
+        if a == b {
+            return Err(Error::SameOwner);
+        }
+        let owner_a = get_owner(&env, a).ok_or(Error::TokenNotFound)?;
+        if owner_a != offerer {
+            return Err(Error::NotOwner);
+        }
+        let owner_b = get_owner(&env, b).ok_or(Error::TokenNotFound)?;
+        if owner_b == offerer {
+            return Err(Error::SameOwner);
+        }
+        ensure_unlocked(&env, a)?;
+        ensure_exclusivity_over(&env, a)?;
+        if retired(&env, a) || retired(&env, b) {
+            return Err(Error::TokenRetired);
+        }
+        ensure_royalty_free(&env, a)?;
+
+        let nonce: u64 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u64>(&MarketKey::HybridSwapNonce)
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::HybridSwapNonce, &nonce);
+        let swap_id = Bytes::from_array(&env, &nonce.to_be_bytes());
+
+        token_transfer_from(
+            &env,
+            &payment_token,
+            &offerer,
+            &env.current_contract_address(),
+            cash,
+        )?;
+        set_locked(&env, a, true);
+
+        env.storage().instance().set::<DataKey, HybridSwap>(
+            &DataKey::HybridSwap(swap_id.clone()),
+            &HybridSwap {
+                offerer,
+                token_a: a,
+                cash,
+                payment_token,
+                token_b: b,
+                accepter: None,
+                expires_at,
+            },
+        );
+        Ok(swap_id)
+    }
+
+    // Tiền là giá trả cho token B nên royalty của token B được trừ từ tiền escrow.
+    // Token A không có giá nên chỉ đổi được khi creator của nó không đòi royalty
+    // hay phí tối thiểu; điều kiện này được kiểm tra lại lúc chấp nhận.
+    pub fn accept_hybrid_swap(env: Env, accepter: Address, swap_id: Bytes) -> Result<(), Error> {
+        accepter.require_auth();
+        let key = DataKey::HybridSwap(swap_id.clone());
+        let swap = env
+            .storage()
+            .instance()
+            .get::<DataKey, HybridSwap>(&key)
+            .ok_or(Error::NotActive)?;
+        if env.ledger().timestamp() > swap.expires_at {
+            return Err(Error::NotActive);
+        }
+        let owner_b = get_owner(&env, swap.token_b).ok_or(Error::TokenNotFound)?;
+        if owner_b != accepter {
+            return Err(Error::NotOwner);
+        }
+        if accepter == swap.offerer {
+            return Err(Error::SameOwner);
+        }
+        ensure_unlocked(&env, swap.token_b)?;
+        ensure_exclusivity_over(&env, swap.token_b)?;
+        check_acl(&env, swap.token_a, &accepter)?;
+        check_acl(&env, swap.token_b, &swap.offerer)?;
+        ensure_royalty_free(&env, swap.token_a)?;
+
+        resolve_sale_economics(
+            &env,
+            swap.token_a,
+            0,
+            None,
+            &swap.payment_token,
+            SaleContext::Swap,
+        )?;
+        let econ = resolve_sale_economics(
+            &env,
+            swap.token_b,
+            swap.cash,
+            Some(&swap.offerer),
+            &swap.payment_token,
+            SaleContext::Swap,
+        )?;
+        settle_sale(
+            &env,
+            swap.token_b,
+            &swap.payment_token,
+            &swap.offerer,
+            &accepter,
+            &econ,
+            true,
+        )?;
+        // Phần tiền escrow được giảm nhờ khuyến mãi/thành viên trả lại offerer
+        let refund = swap.cash - econ.price;
+        if refund > 0 {
+            token_transfer(&env, &swap.payment_token, &swap.offerer, refund)?;
+        }
+
+        set_locked(&env, swap.token_a, false);
+        set_owner(&env, swap.token_a, &accepter);
+        set_owner(&env, swap.token_b, &swap.offerer);
+        record_acquisition(&env, swap.token_a);
+        record_acquisition(&env, swap.token_b);
+
+        env.storage().instance().remove(&key);
+
+        env.events().publish(
+            (symbol_short!("hswap"), swap_id),
+            (
+                swap.offerer,
+                accepter,
+                swap.token_a,
+                swap.token_b,
+                swap.cash,
+            ),
+        );
+        Ok(())
+    }
+
+    // Offerer huỷ đề nghị còn đang chờ: hoàn tiền và mở khoá token A
+    pub fn cancel_hybrid_swap(env: Env, offerer: Address, swap_id: Bytes) -> Result<(), Error> {
+        offerer.require_auth();
+        let key = DataKey::HybridSwap(swap_id);
+        let swap = env
+            .storage()
+            .instance()
+            .get::<DataKey, HybridSwap>(&key)
+            .ok_or(Error::NotActive)?;
+        if swap.offerer != offerer {
+            return Err(Error::NotAuthorized);
+        }
+        token_transfer(&env, &swap.payment_token, &offerer, swap.cash)?;
+        set_locked(&env, swap.token_a, false);
+        env.storage().instance().remove(&key);
+        Ok(())
+    }
+
+    pub fn get_hybrid_swap(env: Env, swap_id: Bytes) -> Option<HybridSwap> {
+        env.storage()
+            .instance()
+            .get::<DataKey, HybridSwap>(&DataKey::HybridSwap(swap_id))
+    }
+
+    // Chốt số token hiện có của creator; điểm được phát dần bằng process_airdrop
+    pub fn start_holder_airdrop(
+        env: Env,
+        creator: Address,
+        points_per_token: u128,
+    ) -> Result<u64, Error> {
+        creator.require_auth();
+        if points_per_token == 0 {
+            return Err(Error::ZeroAmount);
+        }
+        let tokens = creator_tokens(&env, &creator);
+        let snapshot_len = tokens.len();
+
+        let airdrop_id: u64 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u64>(&MarketKey::AirdropNonce)
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::AirdropNonce, &airdrop_id);
+        env.storage().instance().set::<MarketKey, Airdrop>(
+            &MarketKey::Airdrop(airdrop_id),
+            &Airdrop {
+                creator,
+                points_per_token,
+                tokens,
+                snapshot_len,
+                cursor: 0,
+            },
+        );
+        Ok(airdrop_id)
+    }
+
+    // Ai cũng có thể gọi. Điểm được cấp cho chủ sở hữu tại thời điểm xử lý, cursor
+    // chỉ tiến lên nên mỗi token trong snapshot được xử lý đúng một lần, kể cả khi
+    // token đổi chủ giữa các lần gọi. Bỏ qua token creator còn giữ và token đã
+    // export (không còn owner); token retire vẫn có người giữ nên vẫn được nhận.
+    pub fn process_airdrop(env: Env, airdrop_id: u64, limit: u32) -> Result<u32, Error> {
+        let key = MarketKey::Airdrop(airdrop_id);
+        let mut airdrop = env
+            .storage()
+            .instance()
+            .get::<MarketKey, Airdrop>(&key)
+            .ok_or(Error::NotActive)?;
+        if airdrop.cursor >= airdrop.snapshot_len {
+            return Err(Error::NotActive);
+        }
+
+        let current_creator = resolve_creator(&env, airdrop.creator.clone());
+        let end = airdrop
+            .cursor
+            .saturating_add(limit)
+            .min(airdrop.snapshot_len);
+        let mut processed: u32 = 0;
+        while airdrop.cursor < end {
+            if let Some(id) = airdrop.tokens.get(airdrop.cursor) {
+                if let Some(owner) = get_owner(&env, id) {
+                    if owner != airdrop.creator && owner != current_creator {
+                        add_reward_points(&env, &owner, airdrop.points_per_token)?;
+                    }
+                }
+            }
+            airdrop.cursor += 1;
+            processed += 1;
+        }
+        env.storage()
+            .instance()
+            .set::<MarketKey, Airdrop>(&key, &airdrop);
+        if airdrop.cursor == airdrop.snapshot_len {
+            env.events()
+                .publish((symbol_short!("drop_done"), airdrop_id), airdrop.creator);
+        }
+        Ok(processed)
+    }
+
+    pub fn get_airdrop(env: Env, airdrop_id: u64) -> Option<Airdrop> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, Airdrop>(&MarketKey::Airdrop(airdrop_id))
+    }
+
+    // Chuyển token sang chế độ đồng sở hữu. Cổ phần tính theo bps, tổng 10_000;
+    // threshold mặc định 5_000 (cần hơn một nửa).
+    pub fn fractionalize_control(
+        env: Env,
+        owner: Address,
+        token_id: TokenId,
+        holders: Vec<(Address, u32)>,
+        threshold_bps: Option<u32>,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_owner(&env, id, &owner)?;
+        ensure_unlocked(&env, id)?;
+        let threshold_bps = threshold_bps.unwrap_or(5_000);
+        if holders.is_empty() || holders.len() > MAX_SHARE_HOLDERS || threshold_bps >= 10_000 {
+            return Err(Error::InvalidSplit);
+        }
+        let mut seen: Vec<Address> = Vec::new(&env);
+        let mut total: u32 = 0;
+        for (holder, share) in holders.iter() {
+            if share == 0 || seen.contains(&holder) {
+                return Err(Error::InvalidSplit);
+            }
+            seen.push_back(holder);
+            total = total.checked_add(share).ok_or(Error::InvalidSplit)?;
+        }
+        if total != 10_000 {
+            return Err(Error::InvalidSplit);
+        }
+
+        env.storage().instance().set::<MarketKey, SharedControl>(
+            &MarketKey::SharedControl(id),
+            &SharedControl {
+                holders,
+                threshold_bps,
+                pending: Vec::new(&env),
+            },
+        );
+        set_owner(&env, id, &env.current_contract_address());
+        set_locked(&env, id, true);
+        env.events().publish((symbol_short!("shared"), id), owner);
+        Ok(())
+    }
+
+    // Mã hành động mà các holder ký duyệt: sha256(xdr(action, token_id, counterparty, price)).
+    // action là "defrac" (price = 0) hoặc "sell".
+    pub fn get_shared_action_hash(
+        env: Env,
+        token_id: TokenId,
+        action: Symbol,
+        counterparty: Address,
+        price: i128,
+    ) -> BytesN<32> {
+        shared_action_hash(&env, token_id.0, action, &counterparty, price)
+    }
+
+    pub fn approve_action(
+        env: Env,
+        token_id: TokenId,
+        holder: Address,
+        action_hash: BytesN<32>,
+    ) -> Result<(), Error> {
+        holder.require_auth();
+        let id = token_id.0;
+        let key = MarketKey::SharedControl(id);
+        let mut control = env
+            .storage()
+            .instance()
+            .get::<MarketKey, SharedControl>(&key)
+            .ok_or(Error::NotActive)?;
+        if !control.holders.iter().any(|(h, _)| h == holder) {
+            return Err(Error::NotAuthorized);
+        }
+
+        let approvals_key = MarketKey::ActionApprovals(id, action_hash.clone());
+        let mut approvals = action_approvals(&env, id, &action_hash);
+        if !approvals.contains(&holder) {
+            approvals.push_back(holder);
+            env.storage()
+                .instance()
+                .set::<MarketKey, Vec<Address>>(&approvals_key, &approvals);
+        }
+        if !control.pending.contains(&action_hash) {
+            control.pending.push_back(action_hash);
+            env.storage()
+                .instance()
+                .set::<MarketKey, SharedControl>(&key, &control);
+        }
+        Ok(())
+    }
+
+    // Thoát chế độ đồng sở hữu, giao token cho new_owner (có thể là một holder)
+    pub fn defractionalize(env: Env, token_id: TokenId, new_owner: Address) -> Result<(), Error> {
+        let id = token_id.0;
+        let hash = shared_action_hash(&env, id, symbol_short!("defrac"), &new_owner, 0);
+        require_shared_approval(&env, id, &hash)?;
+        ensure_no_snapshot(&env)?;
+        end_shared_control(&env, id);
+        set_owner(&env, id, &new_owner);
+        env.events()
+            .publish((symbol_short!("unshared"), id), new_owner);
+        Ok(())
+    }
+
+    // Bán token đồng sở hữu với giá đã được duyệt; phần của seller và điểm seller
+    // chia theo cổ phần (holder cuối nhận phần dư do làm tròn). Token luôn bị khoá
+    // bởi chính SharedControl nên chỉ kiểm tra khoá snapshot, không dùng ensure_unlocked.
+    pub fn execute_shared_sale(
+        env: Env,
+        token_id: TokenId,
+        buyer: Address,
+        price: i128,
+        payment_token: Option<Address>,
+    ) -> Result<(), Error> {
+        buyer.require_auth();
+        if price <= 0 {
+            return Err(Error::InvalidPrice);
+        }
+        let id = token_id.0;
+        let hash = shared_action_hash(&env, id, symbol_short!("sell"), &buyer, price);
+        let control = require_shared_approval(&env, id, &hash)?;
+        check_acl(&env, id, &buyer)?;
+        ensure_no_snapshot(&env)?;
+        ensure_exclusivity_over(&env, id)?;
+        if form_required(&env, id) {
+            return Err(Error::FormResponseRequired);
+        }
+
+        let pay_token = resolve_pay_token(&env, payment_token)?;
+        let escrow = env.current_contract_address();
+        let econ =
+            resolve_sale_economics(&env, id, price, Some(&buyer), &pay_token, SaleContext::Buy)?;
+        settle_sale(&env, id, &pay_token, &buyer, &escrow, &econ, false)?;
+        let price = econ.price;
+
+        let seller_points_rate: u32 = env
+            .storage()
+            .instance()
+            .get::<FanKey, u32>(&FanKey::SellerPointsRate)
+            .unwrap_or(0);
+        let seller_points =
+            safe_mul_div(price, seller_points_rate as i128, 10_000).ok_or(Error::Overflow)?;
+
+        let count = control.holders.len();
+        let mut paid: i128 = 0;
+        let mut points_given: i128 = 0;
+        for (i, (holder, share)) in control.holders.iter().enumerate() {
+            let (part, points) = if i as u32 + 1 == count {
+                (econ.seller_net - paid, seller_points - points_given)
+            } else {
+                (
+                    safe_mul_div(econ.seller_net, share as i128, 10_000).ok_or(Error::Overflow)?,
+                    safe_mul_div(seller_points, share as i128, 10_000).ok_or(Error::Overflow)?,
+                )
+            };
+            paid += part;
+            points_given += points;
+            token_transfer(&env, &pay_token, &holder, part)?;
+            add_reward_points(&env, &holder, points as u128)?;
+        }
+
+        end_shared_control(&env, id);
+        set_owner(&env, id, &buyer);
+        record_acquisition(&env, id);
+        add_reward_points(&env, &buyer, price as u128)?;
+        let form_response_hash: Option<BytesN<32>> = None;
+        env.events().publish(
+            (symbol_short!("sale"), id),
+            (
+                escrow,
+                buyer,
+                price,
+                form_response_hash,
+                econ.dust,
+                econ.waived,
+            ),
+        );
+        Ok(())
+    }
+
+    pub fn get_shared_control(env: Env, token_id: TokenId) -> Option<SharedControl> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, SharedControl>(&MarketKey::SharedControl(token_id.0))
+    }
+
+    pub fn get_action_approvals(
+        env: Env,
+        token_id: TokenId,
+        action_hash: BytesN<32>,
+    ) -> Vec<Address> {
+        action_approvals(&env, token_id.0, &action_hash)
+    }
+
+    // Gộp dần các cặp (token_id, owner) theo thứ tự id vào một hash:
+    //   h0 = 32 byte 0, h_i = sha256(h_{i-1} || token_id (16 byte big-endian) || xdr(owner))
+    // cursor None = bắt đầu từ đầu (bị từ chối khi một lần tích luỹ khác còn dở);
+    // trả về cursor tiếp theo, None khi đã xong.
+    // Không khoá thì snapshot chỉ là best-effort: token đổi chủ giữa các trang
+    // được ghi theo chủ tại thời điểm trang đó chạy.
+    pub fn build_owner_root(
+        env: Env,
+        cursor: Option<u128>,
+        limit: u32,
+    ) -> Result<(Option<u128>, BytesN<32>), Error> {
+        let current = env
+            .storage()
+            .instance()
+            .get::<MarketKey, SnapshotAccumulator>(&MarketKey::SnapshotAccumulator);
+        let mut acc = match cursor {
+            None if current.as_ref().is_some_and(|a| a.next_id <= a.last_id) => {
+                return Err(Error::AlreadyActive);
+            }
+            None => SnapshotAccumulator {
+                next_id: 1,
+                last_id: env
+                    .storage()
+                    .instance()
+                    .get::<DataKey, u128>(&DataKey::NextId)
+                    .unwrap_or(0),
+                hash: BytesN::from_array(&env, &[0u8; 32]),
+            },
+            Some(c) => {
+                let acc = current.ok_or(Error::NotActive)?;
+                if acc.next_id != c {
+                    return Err(Error::NotActive);
+                }
+                acc
+            }
+        };
+
+        let end = acc
+            .next_id
+            .saturating_add(limit as u128)
+            .min(acc.last_id.saturating_add(1));
+        while acc.next_id < end {
+            if let Some(owner) = get_owner(&env, acc.next_id) {
+                let mut data = Bytes::from_array(&env, &acc.hash.to_array());
+                data.extend_from_array(&acc.next_id.to_be_bytes());
+                data.append(&owner.to_xdr(&env));
+                acc.hash = env.crypto().sha256(&data).into();
+            }
+            acc.next_id += 1;
+        }
+        env.storage()
+            .instance()
+            .set::<MarketKey, SnapshotAccumulator>(&MarketKey::SnapshotAccumulator, &acc);
+
+        let next = if acc.next_id > acc.last_id {
+            None
+        } else {
+            Some(acc.next_id)
+        };
+        Ok((next, acc.hash))
+    }
+
+    // Khoá mọi thay đổi chủ sở hữu qua các đường kiểm tra khoá cho đến khi finalize
+    pub fn set_snapshot_lock(env: Env, admin: Address, locked: bool) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        if locked {
+            env.storage()
+                .instance()
+                .set::<MarketKey, bool>(&MarketKey::SnapshotLock, &true);
+        } else {
+            env.storage().instance().remove(&MarketKey::SnapshotLock);
+        }
+        log_admin_action(&env, symbol_short!("snap_lock"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    pub fn finalize_owner_root(env: Env) -> Result<(u64, BytesN<32>), Error> {
+        let acc = env
+            .storage()
+            .instance()
+            .get::<MarketKey, SnapshotAccumulator>(&MarketKey::SnapshotAccumulator)
+            .ok_or(Error::NotActive)?;
+        if acc.next_id <= acc.last_id {
+            return Err(Error::NotActive);
+        }
+
+        let snapshot_id: u64 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u64>(&MarketKey::OwnerRootNonce)
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::OwnerRootNonce, &snapshot_id);
+        env.storage().instance().set::<MarketKey, OwnerRoot>(
+            &MarketKey::OwnerRoot(snapshot_id),
+            &OwnerRoot {
+                root: acc.hash.clone(),
+                ledger: env.ledger().sequence(),
+                token_count: acc.last_id,
+            },
+        );
+        env.storage()
+            .instance()
+            .remove(&MarketKey::SnapshotAccumulator);
+        env.storage().instance().remove(&MarketKey::SnapshotLock);
+
+        env.events()
+            .publish((symbol_short!("own_root"), snapshot_id), acc.hash.clone());
+        Ok((snapshot_id, acc.hash))
+    }
+
+    pub fn get_owner_root(env: Env, snapshot_id: u64) -> Option<OwnerRoot> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, OwnerRoot>(&MarketKey::OwnerRoot(snapshot_id))
+    }
+
+    // Admin hoặc bot được cấp quyền "claim_pts"
+    pub fn set_claim_points(env: Env, caller: Address, points: u128) -> Result<(), Error> {
+        let action = symbol_short!("claim_pts");
+        require_admin_or_bot(&env, &caller, &action, points)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u128>(&MarketKey::ClaimPoints, &points);
+        log_admin_action(&env, action, &caller, LogSubject::None);
+        Ok(())
+    }
+
+    // Creator mở đợt nhận miễn phí trên các token mình vẫn đang giữ; token bị
+    // khoá cho đến khi được nhận hoặc đợt kết thúc.
+    pub fn create_claim(
+        env: Env,
+        creator: Address,
+        token_ids: Vec<TokenId>,
+        max_per_wallet: u32,
+        start_ledger: u32,
+        end_ledger: u32,
+    ) -> Result<u64, Error> {
+        creator.require_auth();
+        if start_ledger > end_ledger || end_ledger < env.ledger().sequence() {
+            return Err(Error::InvalidWindow);
+        }
+        if token_ids.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        if max_per_wallet == 0 {
+            return Err(Error::ZeroAmount);
+        }
+
+        let mut ids: Vec<u128> = Vec::new(&env);
+        for token_id in token_ids.iter() {
+            let id = token_id.0;
+            let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+            if owner != creator {
+                return Err(Error::NotOwner);
+            }
+            let token_creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
+            if token_creator != creator {
+                return Err(Error::NotAuthorized);
+            }
+            ensure_unlocked(&env, id)?;
+            set_locked(&env, id, true);
+            ids.push_back(id);
+        }
+
+        let claim_id: u64 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u64>(&MarketKey::ClaimNonce)
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::ClaimNonce, &claim_id);
+        env.storage().instance().set::<MarketKey, Claim>(
+            &MarketKey::Claim(claim_id),
+            &Claim {
+                creator,
+                token_ids: ids,
+                next_index: 0,
+                max_per_wallet,
+                start_ledger,
+                end_ledger,
+            },
+        );
+        Ok(claim_id)
+    }
+
+    pub fn claim_free(env: Env, claim_id: u64, fan: Address) -> Result<TokenId, Error> {
+        fan.require_auth();
+        let key = MarketKey::Claim(claim_id);
+        let mut claim = env
+            .storage()
+            .instance()
+            .get::<MarketKey, Claim>(&key)
+            .ok_or(Error::NotActive)?;
+        let ledger = env.ledger().sequence();
+        if ledger < claim.start_ledger || ledger > claim.end_ledger {
+            return Err(Error::NotActive);
+        }
+        if claim.next_index >= claim.token_ids.len() {
+            return Err(Error::BudgetExhausted);
+        }
+        // Token trong claim đang bị khoá bởi chính claim, chỉ cần kiểm tra snapshot
+        ensure_no_snapshot(&env)?;
+        let count_key = MarketKey::ClaimCount(claim_id, fan.clone());
+        let claimed: u32 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u32>(&count_key)
+            .unwrap_or(0);
+        if claimed >= claim.max_per_wallet {
+            return Err(Error::CapExceeded);
+        }
+
+        let id = claim
+            .token_ids
+            .get(claim.next_index)
+            .ok_or(Error::TokenNotFound)?;
+        claim.next_index += 1;
+        env.storage()
+            .instance()
+            .set::<MarketKey, Claim>(&key, &claim);
+        env.storage()
+            .instance()
+            .set::<MarketKey, u32>(&count_key, &(claimed + 1));
+
+        set_locked(&env, id, false);
+        set_owner(&env, id, &fan);
+
+        let points: u128 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u128>(&MarketKey::ClaimPoints)
+            .unwrap_or(0);
+        add_reward_points(&env, &fan, points)?;
+
+        env.events()
+            .publish((symbol_short!("claim"), id), (claim_id, claim.creator, fan));
+        Ok(TokenId(id))
+    }
+
+    // Sau khi đợt kết thúc, ai cũng có thể gọi để mở khoá token chưa được nhận
+    pub fn close_claim(env: Env, claim_id: u64) -> Result<u32, Error> {
+        let key = MarketKey::Claim(claim_id);
+        let claim = env
+            .storage()
+            .instance()
+            .get::<MarketKey, Claim>(&key)
+            .ok_or(Error::NotActive)?;
+        if env.ledger().sequence() <= claim.end_ledger {
+            return Err(Error::AlreadyActive);
+        }
+
+        let mut unlocked: u32 = 0;
+        for i in claim.next_index..claim.token_ids.len() {
+            if let Some(id) = claim.token_ids.get(i) {
+                set_locked(&env, id, false);
+                unlocked += 1;
+            }
+        }
+        env.storage().instance().remove(&key);
+        Ok(unlocked)
+    }
+
+    pub fn get_claim(env: Env, claim_id: u64) -> Option<Claim> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, Claim>(&MarketKey::Claim(claim_id))
+    }
+
+    pub fn is_locked(env: Env, token_id: TokenId) -> bool {
+        token_locked(&env, token_id.0)
+    }
+
+    // Giảm giá theo lịch cho mọi token của creator, áp dụng lúc buy/simulate_buy.
+    // Các đợt của một creator không được chồng lên nhau.
+    pub fn schedule_promo(
+        env: Env,
+        creator: Address,
+        discount_bps: u32,
+        start_ledger: u32,
+        end_ledger: u32,
+    ) -> Result<u64, Error> {
+        creator.require_auth();
+        if discount_bps == 0 || discount_bps >= 10_000 {
+            return Err(Error::BpsOutOfRange);
+        }
+        if start_ledger > end_ledger || end_ledger < env.ledger().sequence() {
+            return Err(Error::InvalidWindow);
+        }
+
+        // Bỏ các đợt đã kết thúc trước khi kiểm tra giới hạn
+        let ledger = env.ledger().sequence();
+        let mut promos: Vec<Promo> = Vec::new(&env);
+        for p in promos_of(&env, &creator).iter() {
+            if p.end_ledger >= ledger {
+                if p.start_ledger <= end_ledger && start_ledger <= p.end_ledger {
+                    return Err(Error::InvalidWindow);
+                }
+                promos.push_back(p);
+            }
+        }
+        if promos.len() >= MAX_PROMOS_PER_CREATOR {
+            return Err(Error::CapExceeded);
+        }
+
+        let promo_id: u64 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u64>(&MarketKey::PromoNonce)
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::PromoNonce, &promo_id);
+        promos.push_back(Promo {
+            promo_id,
+            discount_bps,
+            start_ledger,
+            end_ledger,
+        });
+        env.storage()
+            .instance()
+            .set::<MarketKey, Vec<Promo>>(&MarketKey::Promos(creator.clone()), &promos);
+
+        env.events().publish(
+            (symbol_short!("promo"), creator),
+            (promo_id, discount_bps, start_ledger, end_ledger),
+        );
+        Ok(promo_id)
+    }
+
+    pub fn cancel_promo(env: Env, creator: Address, promo_id: u64) -> Result<(), Error> {
+        creator.require_auth();
+        let mut promos = promos_of(&env, &creator);
+        let i = promos
+            .iter()
+            .position(|p| p.promo_id == promo_id)
+            .ok_or(Error::NotActive)?;
+        promos.remove(i as u32);
+        env.storage()
+            .instance()
+            .set::<MarketKey, Vec<Promo>>(&MarketKey::Promos(creator.clone()), &promos);
+        env.events()
+            .publish((symbol_short!("promo_end"), creator), promo_id);
+        Ok(())
+    }
+
+    pub fn get_promos(env: Env, creator: Address) -> Vec<Promo> {
+        promos_of(&env, &creator)
+    }
+
+    // Thời gian tối thiểu (giây) fan phải giữ token của creator trước khi bán lại
+    pub fn set_exclusivity_period(env: Env, creator: Address, period: u64) {
+        creator.require_auth();
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::ExclusivityPeriod(creator), &period);
+    }
+
+    pub fn get_exclusivity_end(env: Env, token_id: TokenId) -> Option<u64> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, u64>(&MarketKey::ExclusivityEnd(token_id.0))
+    }
+
+    // Số ledger một lần miễn royalty còn hiệu lực nếu chưa dùng
+    pub fn set_waiver_ttl(env: Env, admin: Address, ledgers: u32) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u32>(&MarketKey::WaiverTtl, &ledgers);
+        log_admin_action(&env, symbol_short!("waiv_ttl"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    // Miễn royalty cho lần bán kế tiếp của token (mọi đường thanh toán).
+    // Token có co-creator thì cả hai phải cùng ký.
+    pub fn waive_royalty_once(env: Env, creator: Address, token_id: TokenId) -> Result<(), Error> {
+        let id = token_id.0;
+        require_all_creators(&env, id, &creator)?;
+        let ttl: u32 = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u32>(&MarketKey::WaiverTtl)
+            .unwrap_or(LEDGERS_PER_WEEK);
+        let until = env.ledger().sequence().saturating_add(ttl);
+        env.storage()
+            .instance()
+            .set::<MarketKey, u32>(&MarketKey::RoyaltyWaiver(id), &until);
+        env.events().publish((symbol_short!("waiver"), id), until);
+        Ok(())
+    }
+
+    pub fn revoke_royalty_waiver(
+        env: Env,
+        creator: Address,
+        token_id: TokenId,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_all_creators(&env, id, &creator)?;
+        env.storage()
+            .instance()
+            .remove(&MarketKey::RoyaltyWaiver(id));
+        env.events()
+            .publish((symbol_short!("waiv_rev"), id), creator);
+        Ok(())
+    }
+
+    // Ledger hết hạn của lần miễn royalty đang chờ, None nếu không có hoặc đã hết hạn
+    pub fn get_royalty_waiver(env: Env, token_id: TokenId) -> Option<u32> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, u32>(&MarketKey::RoyaltyWaiver(token_id.0))
+            .filter(|until| env.ledger().sequence() <= *until)
+    }
+
+    pub fn set_min_listing_fee(env: Env, creator: Address, min_fee: i128) -> Result<(), Error> {
+        creator.require_auth();
+        if min_fee < 0 {
+            return Err(Error::InvalidPrice);
+        }
+        env.storage()
+            .instance()
+            .set::<MarketKey, i128>(&MarketKey::CreatorMinListingFee(creator), &min_fee);
+        Ok(())
+    }
+
+    pub fn get_min_listing_fee(env: Env, creator: Address) -> i128 {
+        env.storage()
+            .instance()
+            .get::<MarketKey, i128>(&MarketKey::CreatorMinListingFee(creator))
+            .unwrap_or(0)
+    }
+
+    // Owner hoặc creator đều có thể retire. Với token collab, creator cần cả
+    // hai co-creator cùng ký vì đây là thao tác không đảo ngược được, kể cả
+    // khi creator đó đang giữ token.
+    pub fn retire_token(
+        env: Env,
+        creator_or_owner: Address,
+        token_id: TokenId,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        let owner = get_owner(&env, id).ok_or(Error::TokenNotFound)?;
+        let creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
+        let co_creator = get_co_creator(&env, id).map(|c| c.co_creator);
+
+        if creator_or_owner == creator || Some(creator_or_owner.clone()) == co_creator {
+            require_all_creators(&env, id, &creator_or_owner)?;
+        } else if creator_or_owner == owner {
+            owner.require_auth();
+        } else {
+            return Err(Error::NotAuthorized);
+        }
+
+        env.storage()
+            .instance()
+            .set::<MarketKey, bool>(&MarketKey::Retired(id), &true);
+        env.events()
+            .publish((symbol_short!("retired"), id), creator_or_owner);
+        Ok(())
+    }
+
+    pub fn is_retired(env: Env, token_id: TokenId) -> bool {
+        retired(&env, token_id.0)
+    }
+
+    pub fn set_dust_threshold(
+        env: Env,
+        admin: Address,
+        token: Address,
+        threshold: i128,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        ensure_not_this_contract(&env, &token)?;
+        // 0 = tắt gộp dust
+        if threshold < 0 {
+            return Err(Error::InvalidPrice);
+        }
+        if threshold > MAX_DUST_THRESHOLD {
+            return Err(Error::CapExceeded);
+        }
+        env.storage()
+            .instance()
+            .set::<MarketKey, i128>(&MarketKey::DustThreshold(token.clone()), &threshold);
+        log_admin_action(
+            &env,
+            symbol_short!("dust"),
+            &admin,
+            LogSubject::Address(token),
+        );
+        Ok(())
+    }
+
+    pub fn get_last_royalty_payment(env: Env, token_id: TokenId) -> Option<RoyaltyPayment> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, RoyaltyPayment>(&MarketKey::LastRoyaltyPayment(token_id.0))
+    }
+
+    pub fn set_royalty_hold_period(env: Env, admin: Address, seconds: u64) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::RoyaltyHoldPeriod, &seconds);
+        log_admin_action(&env, symbol_short!("hold"), &admin, LogSubject::None);
+        Ok(())
+    }
+
+    // Rút các khoản royalty đã hết thời gian giữ của creator trên token này,
+    // trả về số khoản đã rút.
+    pub fn claim_pending_royalty(
+        env: Env,
+        creator: Address,
+        token_id: TokenId,
+    ) -> Result<u32, Error> {
+        creator.require_auth();
+        let id = token_id.0;
+        let now = env.ledger().timestamp();
+        let mut remaining: Vec<PendingRelease> = Vec::new(&env);
+        let mut released: u32 = 0;
+        for p in pending_royalties(&env, id).iter() {
+            if p.creator == creator && now >= p.release_at {
+                token_transfer(&env, &p.payment_token, &creator, p.amount)?;
+                released += 1;
+            } else {
+                remaining.push_back(p);
+            }
+        }
+        if released == 0 {
+            return Err(Error::NotActive);
+        }
+
+        let key = MarketKey::PendingRoyaltyRelease(id);
+        if remaining.is_empty() {
+            env.storage().instance().remove(&key);
+        } else {
+            env.storage()
+                .instance()
+                .set::<MarketKey, Vec<PendingRelease>>(&key, &remaining);
+        }
+        env.events()
+            .publish((symbol_short!("roy_rel"), id), (creator, released));
+        Ok(released)
+    }
+
+    pub fn get_pending_royalties(env: Env, token_id: TokenId) -> Vec<PendingRelease> {
+        pending_royalties(&env, token_id.0)
+    }
+
+    pub fn get_total_royalty_paid(env: Env, token_id: TokenId) -> i128 {
+        env.storage()
+            .instance()
+            .get::<MarketKey, i128>(&MarketKey::TotalRoyaltyPaid(token_id.0))
+            .unwrap_or(0)
+    }
+
+    pub fn set_accept_returns(env: Env, creator: Address, accept: bool, consolation_points: u128) {
+        creator.require_auth();
+        let key = MarketKey::AcceptReturns(creator);
+        if accept {
+            env.storage()
+                .instance()
+                .set::<MarketKey, u128>(&key, &consolation_points);
+        } else {
+            env.storage().instance().remove(&key);
+        }
+    }
+
+    pub fn accepts_returns(env: Env, creator: Address) -> Option<u128> {
+        env.storage()
+            .instance()
+            .get::<MarketKey, u128>(&MarketKey::AcceptReturns(creator))
+    }
+
+    // Trả token về creator và xoá lần thanh toán royalty gần nhất. Tổng royalty đã
+    // trả là số liệu kế toán nên được giữ lại.
+    pub fn return_to_creator(env: Env, owner: Address, token_id: TokenId) -> Result<(), Error> {
+        let id = token_id.0;
+        require_owner(&env, id, &owner)?;
+        let creator = get_creator(&env, id).ok_or(Error::TokenNotFound)?;
+        if owner == creator {
+            return Err(Error::SameOwner);
+        }
+        ensure_unlocked(&env, id)?;
+        if retired(&env, id) {
+            return Err(Error::TokenRetired);
+        }
+        let consolation = env
+            .storage()
+            .instance()
+            .get::<MarketKey, u128>(&MarketKey::AcceptReturns(creator.clone()))
+            .ok_or(Error::NotAuthorized)?;
+
+        set_owner(&env, id, &creator);
+        env.storage()
+            .instance()
+            .remove(&MarketKey::LastRoyaltyPayment(id));
+        // Hạn độc quyền của fan cũ không còn áp dụng khi creator nhận lại
+        env.storage()
+            .instance()
+            .remove(&MarketKey::ExclusivityEnd(id));
+        add_reward_points(&env, &owner, consolation)?;
+
+        env.events().publish(
+            (symbol_short!("returned"), id),
+            (owner, creator, consolation),
+        );
+        Ok(())
+    }
+
+    // Đóng kỳ sao kê hiện tại. Gọi lại trong cùng ledger trả về cùng id kỳ.
+    pub fn close_statement_period(env: Env, admin: Address) -> Result<u64, Error> {
+        require_admin(&env, &admin)?;
+        let ledger = env.ledger().sequence();
+        let period = current_period(&env);
+        if env
+            .storage()
+            .instance()
+            .get::<MarketKey, u32>(&MarketKey::PeriodClosedAt)
+            == Some(ledger)
+        {
+            return Ok(period);
+        }
+
+        let next = period + 1;
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::StatementPeriod, &next);
+        env.storage()
+            .instance()
+            .set::<MarketKey, u32>(&MarketKey::PeriodClosedAt, &ledger);
+        env.events().publish((symbol_short!("period"),), next);
+        log_admin_action(&env, symbol_short!("period"), &admin, LogSubject::None);
+        Ok(next)
+    }
+
+    pub fn get_statement_period(env: Env) -> u64 {
+        current_period(&env)
+    }
+
+    pub fn get_statement(env: Env, creator: Address, period_id: u64) -> Statement {
+        statement(&env, &creator, period_id)
+    }
+
+    // Ai cũng có thể xoá sao kê đã ra khỏi cửa sổ lưu giữ
+    pub fn prune_statement(env: Env, creator: Address, period_id: u64) -> Result<(), Error> {
+        if period_id.saturating_add(RETAINED_STATEMENT_PERIODS) > current_period(&env) {
+            return Err(Error::AlreadyActive);
+        }
+        env.storage()
+            .instance()
+            .remove(&MarketKey::Statement(creator, period_id));
+        Ok(())
+    }
+
+    pub fn set_purchase_form(
+        env: Env,
+        creator: Address,
+        token_id: TokenId,
+        form_uri: Bytes,
+        required: bool,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_creator(&env, id, &creator)?;
+        env.storage().instance().set::<DataKey, PurchaseForm>(
+            &DataKey::PurchaseForm(id),
+            &PurchaseForm { form_uri, required },
+        );
+        Ok(())
+    }
+
+    pub fn get_purchase_form(env: Env, token_id: TokenId) -> Option<PurchaseForm> {
+        env.storage()
+            .instance()
+            .get::<DataKey, PurchaseForm>(&DataKey::PurchaseForm(token_id.0))
+    }
+
+    pub fn get_form_response(env: Env, token_id: TokenId, buyer: Address) -> Option<FormResponse> {
+        env.storage()
+            .instance()
+            .get::<DataKey, FormResponse>(&DataKey::FormResponse(token_id.0, buyer))
+    }
+
+    pub fn mark_form_fulfilled(
+        env: Env,
+        creator: Address,
+        token_id: TokenId,
+        buyer: Address,
+    ) -> Result<(), Error> {
+        let id = token_id.0;
+        require_creator(&env, id, &creator)?;
+        let key = DataKey::FormResponse(id, buyer);
+        let mut response = env
+            .storage()
+            .instance()
+            .get::<DataKey, FormResponse>(&key)
+            .ok_or(Error::NotActive)?;
+        response.fulfilled = true;
+        env.storage()
+            .instance()
+            .set::<DataKey, FormResponse>(&key, &response);
+        Ok(())
+    }
+
+    // Dump token id trong [start_id, start_id + limit), limit tối đa MAX_DUMP_TOKENS.
+    // None cho id chưa tồn tại.
+    pub fn dump_tokens(
+        env: Env,
+        start_id: u128,
+        limit: u32,
+        include_uris: bool,
+    ) -> Vec<Option<TokenDump>> {
+        let mut out: Vec<Option<TokenDump>> = Vec::new(&env);
+        let limit = limit.min(MAX_DUMP_TOKENS) as u128;
+        let mut id = start_id;
+        while id < start_id.saturating_add(limit) {
+            out.push_back(dump_token(&env, id, include_uris));
+            id += 1;
+        }
+        out
+    }
+
+    pub fn dump_config(env: Env) -> ConfigDump {
+        ConfigDump {
+            default_payment_token: env
+                .storage()
+                .instance()
+                .get::<DataKey, Address>(&DataKey::DefaultPayToken),
+            last_token_id: env
+                .storage()
+                .instance()
+                .get::<DataKey, u128>(&DataKey::NextId)
+                .unwrap_or(0),
+            statement_period: current_period(&env),
+            royalty_hold_period: env
+                .storage()
+                .instance()
+                .get::<MarketKey, u64>(&MarketKey::RoyaltyHoldPeriod)
+                .unwrap_or(0),
+            waiver_ttl: env
+                .storage()
+                .instance()
+                .get::<MarketKey, u32>(&MarketKey::WaiverTtl)
+                .unwrap_or(LEDGERS_PER_WEEK),
+            max_fan_points_per_address: fan_point_setting(&env, FanKey::MaxFanPointsPerAddress),
+            max_total_fan_points: fan_point_setting(&env, FanKey::MaxTotalFanPoints),
+            total_fan_points: fan_point_setting(&env, FanKey::TotalFanPoints),
+            seller_points_rate: env
+                .storage()
+                .instance()
+                .get::<FanKey, u32>(&FanKey::SellerPointsRate)
+                .unwrap_or(0),
+            tip_points_divisor: fan_point_setting(&env, FanKey::TipPointsDivisor),
+            points_transferable: env
+                .storage()
+                .instance()
+                .get::<FanKey, bool>(&FanKey::PointsTransferable)
+                .unwrap_or(false),
+            grant_key_retention: env
+                .storage()
+                .instance()
+                .get::<FanKey, u32>(&FanKey::GrantKeyRetention)
+                .unwrap_or(0),
+            snapshot_locked: env
+                .storage()
+                .instance()
+                .get::<MarketKey, bool>(&MarketKey::SnapshotLock)
+                .unwrap_or(false),
+        }
+    }
+
+    // Chỉ các entrypoint cũ trong legacy_entrypoints mới tắt được;
+    // get_info, transfer và các hàm lõi khác không nằm trong danh sách.
+    pub fn set_entrypoint_enabled(
+        env: Env,
+        admin: Address,
+        func: Symbol,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        require_admin(&env, &admin)?;
+        if !legacy_entrypoints(&env).contains(&func) {
+            return Err(Error::UnknownAdminOp);
+        }
+        let key = MarketKey::EntrypointDisabled(func);
+        if enabled {
+            env.storage().instance().remove(&key);
+        } else {
+            env.storage().instance().set::<MarketKey, bool>(&key, &true);
+        }
+        log_admin_action(&env, symbol_short!("entry"), &admin, LogSubject::None);
+        Ok(())
+    }
 
-        let pay_token = match payment_token {
-            Some(addr) => addr,
-            None => env
+    // (entrypoint, số lần gọi thành công, đang bật)
+    pub fn get_call_counts(env: Env) -> Vec<(Symbol, u64, bool)> {
+        let mut out = Vec::new(&env);
+        for func in legacy_entrypoints(&env).iter() {
+            let count = env
                 .storage()
                 .instance()
-                .get::<DataKey, Address>(&DataKey::DefaultPayToken)
-                .ok_or(Error::InvalidPaymentToken)?,
-        };
+                .get::<DataKey, u64>(&DataKey::CallCount(func.clone()))
+                .unwrap_or(0);
+            let enabled = !env
+                .storage()
+                .instance()
+                .has(&MarketKey::EntrypointDisabled(func.clone()));
+            out.push_back((func, count, enabled));
+        }
+        out
+    }
 
-        let royalty = safe_mul_div(price, royalty_bps as i128, 10_000).ok_or(Error::Overflow)?;
-        let seller_amount = price.checked_sub(royalty).ok_or(Error::Overflow)?;
+    // Dry-run của buy: cùng phép tính, không require_auth, không ghi storage.
+    // Hợp đồng chưa có phí sàn và chưa cho đổi điểm khi mua, nên platform_fee
+    // và points_redeemed luôn bằng 0.
+    pub fn simulate_buy(
+        env: Env,
+        token_id: TokenId,
+        buyer: Address,
+        price: i128,
+        payment_token: Option<Address>,
+        redeem_points: Option<u128>,
+    ) -> Result<BuySimulation, Error> {
+        let _ = redeem_points;
+        // Cùng điều kiện với buy, để quote không hứa một giao dịch sẽ thất bại
+        if env
+            .storage()
+            .instance()
+            .has(&MarketKey::EntrypointDisabled(symbol_short!("buy")))
+        {
+            return Err(Error::EntrypointDisabled);
+        }
+        if price <= 0 {
+            return Err(Error::InvalidPrice);
+        }
 
-        token_transfer_from(&env, &pay_token, &buyer, &creator, royalty)?;
-        token_transfer_from(&env, &pay_token, &buyer, &owner, seller_amount)?;
+        let id = token_id.0;
+        check_buy_allowed(&env, id, &buyer)?;
+        let pay_token = resolve_pay_token(&env, payment_token)?;
+        let econ = resolve_sale_economics(
+            &env,
+            id,
+            price,
+            Some(&buyer),
+            &pay_token,
+            SaleContext::Quote,
+        )?;
+        let effective_price = econ.price;
+        // Điểm của buyer cũng bị cắt theo giới hạn như khi buy thật
+        let (_, _, points_awarded) =
+            fan_points_headroom(&env, &buyer, effective_price as u128, true)?;
 
-        set_owner(&env, id, &buyer);
+        Ok(BuySimulation {
+            royalty: econ.royalty,
+            seller_amount: econ.seller_net,
+            platform_fee: 0,
+            points_awarded,
+            points_redeemed: 0,
+            effective_price,
+            promo_bps: econ.promo_bps,
+            promo_skipped: econ.promo_skipped,
+            form_required: form_required(&env, id),
+        })
+    }
 
-        let points: u128 = if price > 0 { price as u128 } else { 0u128 };
-        add_fan_points(&env, &buyer, points)?;
+    // Số tiền seller thực nhận nếu bán token với giá `price`
+    pub fn net_for_price(
+        env: Env,
+        token_id: TokenId,
+        price: i128,
+        payment_token: Option<Address>,
+    ) -> Result<i128, Error> {
+        if price <= 0 {
+            return Err(Error::InvalidPrice);
+        }
+        let pay_token = resolve_pay_token(&env, payment_token)?;
+        seller_net_at(&env, token_id.0, price, &pay_token)
+    }
 
-        Ok(())
+    // Giá niêm yết để seller nhận ít nhất `desired_net` theo đúng phép tính lúc
+    // thanh toán (kể cả khuyến mãi đang chạy, không tính giảm giá thành viên).
+    // Gộp dust làm số tiền nhận không đơn điệu theo giá, nên việc tìm kiếm chạy
+    // trên phần của seller trước khi gộp: giá trả về là giá nhỏ nhất theo phần
+    // đó. Khi phần seller nhỏ hơn ngưỡng dust và bị gộp mất, tìm lại với đích là
+    // ngưỡng để phần đó không còn là dust. Vì vậy luôn có
+    // net_for_price(kết quả) >= desired_net, nhưng khi phần creator là dust, hoặc
+    // khuyến mãi bị bỏ qua ở gần phí tối thiểu, có thể tồn tại giá thấp hơn cũng thoả.
+    pub fn price_for_net(
+        env: Env,
+        token_id: TokenId,
+        desired_net: i128,
+        payment_token: Option<Address>,
+    ) -> Result<i128, Error> {
+        let id = token_id.0;
+        if desired_net <= 0 {
+            return Err(Error::InvalidPrice);
+        }
+        // royalty 100% thì seller không bao giờ nhận được gì
+        if get_royalty_bps(&env, id).ok_or(Error::TokenNotFound)? >= 10_000 {
+            return Err(Error::InvalidPrice);
+        }
+        let pay_token = resolve_pay_token(&env, payment_token)?;
+
+        let price = min_price_before_dust(&env, id, desired_net)?;
+        if seller_net_at(&env, id, price, &pay_token)? >= desired_net {
+            return Ok(price);
+        }
+        min_price_before_dust(&env, id, dust_threshold(&env, &pay_token))
     }
 }
 
@@ -206,6 +4362,758 @@ impl FanRewardsNftMarket {
 // Internal helpers
 // ===========================
 
+// Admin đã lưu, còn hạn, và đã ký
+fn require_admin(env: &Env, admin: &Address) -> Result<(), Error> {
+    if active_admin(env)? != *admin {
+        return Err(Error::NotAuthorized);
+    }
+    admin.require_auth();
+    Ok(())
+}
+fn active_admin(env: &Env) -> Result<Address, Error> {
+    let admin = env
+        .storage()
+        .instance()
+        .get::<AdminKey, Address>(&AdminKey::Admin)
+        .ok_or(Error::NotAuthorized)?;
+    let expiry: u64 = env
+        .storage()
+        .instance()
+        .get::<AdminKey, u64>(&AdminKey::AdminExpiry)
+        .unwrap_or(0);
+    if env.ledger().timestamp() > expiry {
+        return Err(Error::AdminKeyExpired);
+    }
+    Ok(admin)
+}
+// Key ký off-chain kèm địa chỉ admin đã đặt nó
+fn admin_public_key(env: &Env) -> Option<(Address, BytesN<32>)> {
+    env.storage()
+        .instance()
+        .get::<DataKey, (Address, BytesN<32>)>(&DataKey::AdminPubKey)
+}
+// Ghi admin với nhiệm kỳ tính từ bây giờ, trả về thời điểm hết hạn
+fn write_admin(env: &Env, admin: &Address, term: u64) -> Result<u64, Error> {
+    let expiry = env
+        .ledger()
+        .timestamp()
+        .checked_add(term)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set::<AdminKey, Address>(&AdminKey::Admin, admin);
+    env.storage()
+        .instance()
+        .set::<AdminKey, u64>(&AdminKey::AdminExpiry, &expiry);
+    env.events()
+        .publish((symbol_short!("admin"), admin.clone()), expiry);
+    Ok(expiry)
+}
+// Các setter có thể giao cho bot; tên trùng với action trong admin log
+fn automatable_setters(env: &Env) -> Vec<Symbol> {
+    let mut setters = Vec::new(env);
+    setters.push_back(symbol_short!("sell_pts"));
+    setters.push_back(symbol_short!("tip_div"));
+    setters.push_back(symbol_short!("claim_pts"));
+    setters.push_back(symbol_short!("endorse"));
+    setters
+}
+// Admin đi thẳng; bot cần quyền cho đúng setter và giá trị nằm trong
+// giới hạn. Trả về true nếu người gọi là bot. Bot hành động thay admin nên
+// admin hết hạn thì bot cũng dừng.
+fn require_admin_or_bot(
+    env: &Env,
+    caller: &Address,
+    setter: &Symbol,
+    value: u128,
+) -> Result<bool, Error> {
+    if active_admin(env)? == *caller {
+        caller.require_auth();
+        return Ok(false);
+    }
+    let permissions = env
+        .storage()
+        .instance()
+        .get::<AdminKey, Vec<AutomationPermission>>(&AdminKey::Automation(caller.clone()))
+        .ok_or(Error::NotAuthorized)?;
+    let permission = permissions
+        .iter()
+        .find(|p| p.setter == *setter)
+        .ok_or(Error::NotAuthorized)?;
+    if value < permission.min || value > permission.max {
+        return Err(Error::AutomationLimitExceeded);
+    }
+    caller.require_auth();
+    env.events().publish(
+        (symbol_short!("auto"), caller.clone()),
+        (setter.clone(), value),
+    );
+    Ok(true)
+}
+fn require_council_signer(env: &Env, signer: &Address) -> Result<(), Error> {
+    let council = env
+        .storage()
+        .instance()
+        .get::<AdminKey, Council>(&AdminKey::Council)
+        .ok_or(Error::NotActive)?;
+    if !council.signers.contains(signer) {
+        return Err(Error::NotAuthorized);
+    }
+    signer.require_auth();
+    Ok(())
+}
+// Kiểm tra lúc đề xuất và lần nữa lúc thực thi (state có thể đã đổi)
+fn validate_gov_action(env: &Env, action: &GovAction) -> Result<(), Error> {
+    match action {
+        GovAction::SetAdmin(new_admin, term) => {
+            if *term == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            ensure_not_this_contract(env, new_admin)
+        }
+        // Token đang khoá (claim, hybrid swap, đồng sở hữu, snapshot) giữ nguyên
+        // trạng thái đó; phải gỡ khoá bằng luồng thường trước khi chuyển cưỡng chế.
+        GovAction::EmergencyTransfer(id, to, _) => {
+            let from = get_owner(env, *id).ok_or(Error::TokenNotFound)?;
+            if from == *to {
+                return Err(Error::SameOwner);
+            }
+            ensure_not_this_contract(env, to)?;
+            if env.storage().instance().has(&MarketKey::SharedControl(*id)) {
+                return Err(Error::TokenLocked);
+            }
+            ensure_unlocked(env, *id)?;
+            let max: u32 = env
+                .storage()
+                .instance()
+                .get::<MarketKey, u32>(&MarketKey::MaxEmergencyTransfers)
+                .unwrap_or(0);
+            if emergency_records(env).len() >= max {
+                return Err(Error::CapExceeded);
+            }
+            Ok(())
+        }
+        GovAction::SetMaxEmergencyTransfers(_) => Ok(()),
+    }
+}
+fn store_gov_proposal(
+    env: &Env,
+    proposer: &Address,
+    action: GovAction,
+    approvals: Vec<Address>,
+) -> Result<u64, Error> {
+    let proposal_id: u64 = env
+        .storage()
+        .instance()
+        .get::<AdminKey, u64>(&AdminKey::GovProposalNonce)
+        .unwrap_or(0)
+        .checked_add(1)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set::<AdminKey, u64>(&AdminKey::GovProposalNonce, &proposal_id);
+    env.storage().instance().set::<AdminKey, GovProposal>(
+        &AdminKey::GovProposal(proposal_id),
+        &GovProposal {
+            action,
+            proposer: proposer.clone(),
+            approvals,
+            created_at: env.ledger().timestamp(),
+        },
+    );
+    env.events()
+        .publish((symbol_short!("gov_prop"), proposal_id), proposer.clone());
+    Ok(proposal_id)
+}
+fn emergency_records(env: &Env) -> Vec<EmergencyRecord> {
+    env.storage()
+        .instance()
+        .get::<MarketKey, Vec<EmergencyRecord>>(&MarketKey::EmergencyTransfers)
+        .unwrap_or(Vec::new(env))
+}
+
+fn write_default_payment_token(env: &Env, token: &Address) {
+    env.storage()
+        .instance()
+        .set::<DataKey, Address>(&DataKey::DefaultPayToken, token);
+}
+
+fn write_param(env: &Env, key: Bytes, value: &Bytes) {
+    env.storage()
+        .instance()
+        .set::<DataKey, Bytes>(&DataKey::Param(key), value);
+}
+
+fn resolve_pay_token(env: &Env, payment_token: Option<Address>) -> Result<Address, Error> {
+    let token = match payment_token {
+        Some(addr) => addr,
+        None => env
+            .storage()
+            .instance()
+            .get::<DataKey, Address>(&DataKey::DefaultPayToken)
+            .ok_or(Error::InvalidPaymentToken)?,
+    };
+    // Contract NFT này không phải token thanh toán
+    ensure_not_this_contract(env, &token)?;
+    Ok(token)
+}
+
+// Token hoặc tiền gửi tới chính contract sẽ bị kẹt lại
+fn ensure_not_this_contract(env: &Env, addr: &Address) -> Result<(), Error> {
+    if *addr == env.current_contract_address() {
+        return Err(Error::ContractAddress);
+    }
+    Ok(())
+}
+
+// Đường thanh toán đang gọi resolve_sale_economics
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SaleContext {
+    Buy,
+    Quote,
+    Bundle,
+    Swap,
+}
+
+// Toàn bộ các phần tiền của một lần bán; tổng các phần luôn bằng giá bán
+struct SaleEconomics {
+    // Giá người mua thực trả sau khuyến mãi và giảm giá thành viên
+    price: i128,
+    promo_bps: u32,
+    // Khuyến mãi đang chạy nhưng bị bỏ vì đẩy giá xuống dưới mức tối thiểu
+    promo_skipped: bool,
+    royalty: i128,
+    creator: Address,
+    creator_share: i128,
+    co_creator: Option<Address>,
+    co_creator_share: i128,
+    seller_net: i128,
+    // Các phần nhỏ hơn ngưỡng dust đã được gộp: (phần bị gộp, số tiền)
+    dust: Vec<(Symbol, i128)>,
+    // Royalty được miễn nhờ waive_royalty_once
+    waived: bool,
+}
+
+// Token đổi không kèm tiền: không có giá để tính royalty nên chỉ chấp nhận
+// token có royalty 0 và creator không đặt phí tối thiểu
+fn ensure_royalty_free(env: &Env, id: u128) -> Result<(), Error> {
+    let royalty_bps = get_royalty_bps(env, id).ok_or(Error::TokenNotFound)?;
+    let creator = get_creator(env, id).ok_or(Error::TokenNotFound)?;
+    let min_fee: i128 = env
+        .storage()
+        .instance()
+        .get::<MarketKey, i128>(&MarketKey::CreatorMinListingFee(creator))
+        .unwrap_or(0);
+    if royalty_bps > 0 || min_fee > 0 {
+        return Err(Error::InvalidRoyalty);
+    }
+    Ok(())
+}
+
+// Tính phân chia cho mọi đường thanh toán (buy, simulate_buy, bundle, swap,
+// bán đồng sở hữu) để chúng không bao giờ lệch nhau. `price` là giá niêm yết:
+// khuyến mãi của creator và giảm giá thành viên của người mua (nếu có) được áp
+// ở đây, econ.price là số người mua thực trả. Royalty không thấp hơn mức tối
+// thiểu creator đặt, nhưng cũng không vượt quá giá bán. Đường nào không hỗ trợ
+// một thành phần (vd. form mua hàng bắt buộc) phải báo lỗi thay vì bỏ qua.
+fn resolve_sale_economics(
+    env: &Env,
+    id: u128,
+    price: i128,
+    buyer: Option<&Address>,
+    pay_token: &Address,
+    context: SaleContext,
+) -> Result<SaleEconomics, Error> {
+    let mut econ = economics_before_dust(env, id, price, buyer, context)?;
+    fold_dust(env, pay_token, &mut econ);
+    Ok(econ)
+}
+
+// Chia tiền trước khi gộp dust
+fn economics_before_dust(
+    env: &Env,
+    id: u128,
+    list_price: i128,
+    buyer: Option<&Address>,
+    context: SaleContext,
+) -> Result<SaleEconomics, Error> {
+    if matches!(context, SaleContext::Bundle | SaleContext::Swap) && form_required(env, id) {
+        return Err(Error::FormResponseRequired);
+    }
+    if retired(env, id) {
+        return Err(Error::TokenRetired);
+    }
+    let (price, promo_bps, promo_skipped) = promo_price(env, id, list_price)?;
+    let price = match buyer {
+        Some(b) => member_price(env, id, b, price)?,
+        None => price,
+    };
+
+    let royalty_bps = get_royalty_bps(env, id).ok_or(Error::TokenNotFound)?;
+    let creator = get_creator(env, id).ok_or(Error::TokenNotFound)?;
+    let mut royalty = safe_mul_div(price, royalty_bps as i128, 10_000).ok_or(Error::Overflow)?;
+    let min_fee: i128 = env
+        .storage()
+        .instance()
+        .get::<MarketKey, i128>(&MarketKey::CreatorMinListingFee(creator.clone()))
+        .unwrap_or(0);
+    if royalty < min_fee {
+        royalty = min_fee.min(price);
+    }
+    // Miễn royalty một lần: quote chỉ xem, lần thanh toán đầu tiên có royalty sẽ dùng hết
+    let waiver_key = MarketKey::RoyaltyWaiver(id);
+    let waived = royalty > 0
+        && env
+            .storage()
+            .instance()
+            .get::<MarketKey, u32>(&waiver_key)
+            .is_some_and(|until| env.ledger().sequence() <= until);
+    if waived {
+        royalty = 0;
+        if context != SaleContext::Quote {
+            env.storage().instance().remove(&waiver_key);
+        }
+    }
+
+    let (co_creator, creator_share, co_creator_share) = match get_co_creator(env, id) {
+        Some(c) => {
+            let share_a =
+                safe_mul_div(royalty, c.split_a_bps as i128, 10_000).ok_or(Error::Overflow)?;
+            let share_b = royalty.checked_sub(share_a).ok_or(Error::Overflow)?;
+            (Some(c.co_creator), share_a, share_b)
+        }
+        None => (None, royalty, 0),
+    };
+    let seller_net = price.checked_sub(royalty).ok_or(Error::Overflow)?;
+
+    Ok(SaleEconomics {
+        price,
+        promo_bps,
+        promo_skipped,
+        royalty,
+        creator,
+        creator_share,
+        co_creator,
+        co_creator_share,
+        seller_net,
+        dust: Vec::new(env),
+        waived,
+    })
+}
+
+// Gộp các phần nhỏ hơn ngưỡng dust của payment token vào phần lớn nhất
+// (hoà thì ưu tiên seller, rồi creator, rồi co-creator). Tổng không đổi.
+fn fold_dust(env: &Env, pay_token: &Address, econ: &mut SaleEconomics) {
+    let threshold = dust_threshold(env, pay_token);
+    if threshold <= 0 {
+        return;
+    }
+
+    let names = [
+        symbol_short!("seller"),
+        symbol_short!("creator"),
+        symbol_short!("co_creatr"),
+    ];
+    let mut legs = [econ.seller_net, econ.creator_share, econ.co_creator_share];
+    let mut largest = 0;
+    for (i, leg) in legs.iter().enumerate() {
+        if *leg > legs[largest] {
+            largest = i;
+        }
+    }
+    for (i, name) in names.iter().enumerate() {
+        let amount = legs[i];
+        if i != largest && amount > 0 && amount < threshold {
+            legs[largest] += amount;
+            legs[i] = 0;
+            econ.dust.push_back((name.clone(), amount));
+        }
+    }
+
+    econ.seller_net = legs[0];
+    econ.creator_share = legs[1];
+    econ.co_creator_share = legs[2];
+    econ.royalty = legs[1] + legs[2];
+}
+
+fn dust_threshold(env: &Env, pay_token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get::<MarketKey, i128>(&MarketKey::DustThreshold(pay_token.clone()))
+        .unwrap_or(0)
+}
+
+fn seller_net_at(env: &Env, id: u128, price: i128, pay_token: &Address) -> Result<i128, Error> {
+    resolve_sale_economics(env, id, price, None, pay_token, SaleContext::Quote)
+        .map(|e| e.seller_net)
+}
+
+// Giá có phần seller trước gộp dust >= target: nhân đôi cho tới khi đủ rồi chia
+// đôi. hi luôn thoả nên kết quả luôn thoả; là giá nhỏ nhất khi phần đó đơn điệu
+fn min_price_before_dust(env: &Env, id: u128, target: i128) -> Result<i128, Error> {
+    let net = |price: i128| {
+        economics_before_dust(env, id, price, None, SaleContext::Quote).map(|e| e.seller_net)
+    };
+    let mut lo = target - 1;
+    let mut hi = target;
+    while net(hi)? < target {
+        lo = hi;
+        hi = hi.checked_mul(2).ok_or(Error::Overflow)?;
+    }
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if net(mid)? >= target {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    Ok(hi)
+}
+
+// Chuyển từng phần tiền của một lần bán từ người trả tới người nhận
+fn settle_sale(
+    env: &Env,
+    id: u128,
+    token: &Address,
+    payer: &Address,
+    seller: &Address,
+    econ: &SaleEconomics,
+    from_escrow: bool,
+) -> Result<(), Error> {
+    // from_escrow: tiền của payer đã nằm trong contract (vd. hybrid swap)
+    let pay = |to: &Address, amount: i128| {
+        if from_escrow {
+            token_transfer(env, token, to, amount)
+        } else {
+            token_transfer_from(env, token, payer, to, amount)
+        }
+    };
+    // Trong thời gian giữ, royalty nằm trong contract chờ creator rút
+    let hold: u64 = env
+        .storage()
+        .instance()
+        .get::<MarketKey, u64>(&MarketKey::RoyaltyHoldPeriod)
+        .unwrap_or(0);
+    let pay_royalty = |to: &Address, amount: i128| {
+        if hold == 0 || amount <= 0 {
+            return pay(to, amount);
+        }
+        if !from_escrow {
+            token_transfer_from(env, token, payer, &env.current_contract_address(), amount)?;
+        }
+        let release_at = env
+            .ledger()
+            .timestamp()
+            .checked_add(hold)
+            .ok_or(Error::Overflow)?;
+        let mut pending = pending_royalties(env, id);
+        pending.push_back(PendingRelease {
+            amount,
+            release_at,
+            payment_token: token.clone(),
+            creator: to.clone(),
+        });
+        env.storage()
+            .instance()
+            .set::<MarketKey, Vec<PendingRelease>>(&MarketKey::PendingRoyaltyRelease(id), &pending);
+        Ok(())
+    };
+    pay_royalty(&econ.creator, econ.creator_share)?;
+    if let Some(co) = &econ.co_creator {
+        pay_royalty(co, econ.co_creator_share)?;
+    }
+    pay(seller, econ.seller_net)?;
+    record_royalty_payment(env, id, payer, econ)?;
+    record_statement_sale(env, seller, econ)
+}
+
+fn dump_token(env: &Env, id: u128, include_uris: bool) -> Option<TokenDump> {
+    let owner = get_owner(env, id)?;
+    let creator = get_creator(env, id)?;
+    let (uri, backup, links) = if include_uris {
+        (
+            get_uri(env, id),
+            backup_uri(env, id),
+            external_links(env, id),
+        )
+    } else {
+        (None, None, Vec::new(env))
+    };
+    let (co_creator, split_a_bps) = co_creator_split(env, id);
+    let last = env
+        .storage()
+        .instance()
+        .get::<MarketKey, RoyaltyPayment>(&MarketKey::LastRoyaltyPayment(id));
+    Some(TokenDump {
+        token_id: id,
+        owner,
+        creator,
+        royalty_bps: get_royalty_bps(env, id).unwrap_or(0),
+        minted_at: mint_timestamp(env, id).unwrap_or(0),
+        co_creator,
+        split_a_bps,
+        uri,
+        backup_uri: backup,
+        external_links: links,
+        locked: token_locked(env, id),
+        retired: retired(env, id),
+        metadata_frozen: metadata_frozen(env, id),
+        last_royalty_buyer: last.as_ref().map(|p| p.buyer.clone()),
+        last_royalty_paid_to: last.as_ref().map(|p| p.paid_to.clone()),
+        last_royalty_amount: last.as_ref().map_or(0, |p| p.amount),
+        last_royalty_ledger: last.as_ref().map_or(0, |p| p.at_ledger),
+        total_royalty_paid: env
+            .storage()
+            .instance()
+            .get::<MarketKey, i128>(&MarketKey::TotalRoyaltyPaid(id))
+            .unwrap_or(0),
+    })
+}
+
+// Entrypoint cũ đang được đo để ngừng hỗ trợ (buy với giá tuỳ ý)
+fn legacy_entrypoints(env: &Env) -> Vec<Symbol> {
+    let mut funcs = Vec::new(env);
+    funcs.push_back(symbol_short!("buy"));
+    funcs
+}
+// Gọi ở đầu entrypoint cũ: báo lỗi nếu đã tắt, nếu không thì đếm lượt gọi.
+// Lượt gọi lỗi bị huỷ cùng giao dịch nên chỉ lượt thành công được đếm.
+fn track_legacy_call(env: &Env, func: Symbol) -> Result<(), Error> {
+    if env
+        .storage()
+        .instance()
+        .has(&MarketKey::EntrypointDisabled(func.clone()))
+    {
+        return Err(Error::EntrypointDisabled);
+    }
+    let key = DataKey::CallCount(func);
+    let count: u64 = env
+        .storage()
+        .instance()
+        .get::<DataKey, u64>(&key)
+        .unwrap_or(0)
+        .saturating_add(1);
+    env.storage().instance().set::<DataKey, u64>(&key, &count);
+    Ok(())
+}
+
+fn pending_royalties(env: &Env, id: u128) -> Vec<PendingRelease> {
+    env.storage()
+        .instance()
+        .get::<MarketKey, Vec<PendingRelease>>(&MarketKey::PendingRoyaltyRelease(id))
+        .unwrap_or(Vec::new(env))
+}
+
+fn current_period(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get::<MarketKey, u64>(&MarketKey::StatementPeriod)
+        .unwrap_or(0)
+}
+fn statement(env: &Env, creator: &Address, period: u64) -> Statement {
+    env.storage()
+        .instance()
+        .get::<MarketKey, Statement>(&MarketKey::Statement(creator.clone(), period))
+        .unwrap_or(Statement {
+            royalty_earned: 0,
+            primary_sales: 0,
+            secondary_sales: 0,
+            tips: 0,
+            fees_paid: 0,
+            per_token_breakdown_count: 0,
+        })
+}
+fn set_statement(env: &Env, creator: &Address, period: u64, statement: &Statement) {
+    env.storage()
+        .instance()
+        .set::<MarketKey, Statement>(&MarketKey::Statement(creator.clone(), period), statement);
+}
+
+// Ghi lần bán vào sao kê kỳ hiện tại; co-creator chỉ được ghi phần của mình
+fn record_statement_sale(env: &Env, seller: &Address, econ: &SaleEconomics) -> Result<(), Error> {
+    let period = current_period(env);
+    let price = econ
+        .royalty
+        .checked_add(econ.seller_net)
+        .ok_or(Error::Overflow)?;
+    let primary = *seller == econ.creator || econ.co_creator.as_ref() == Some(seller);
+
+    let mut st = statement(env, &econ.creator, period);
+    st.royalty_earned = st
+        .royalty_earned
+        .checked_add(econ.creator_share)
+        .ok_or(Error::Overflow)?;
+    if *seller == econ.creator {
+        st.primary_sales = st
+            .primary_sales
+            .checked_add(econ.seller_net)
+            .ok_or(Error::Overflow)?;
+    } else if !primary {
+        st.secondary_sales = st
+            .secondary_sales
+            .checked_add(price)
+            .ok_or(Error::Overflow)?;
+    }
+    st.per_token_breakdown_count = st.per_token_breakdown_count.saturating_add(1);
+    set_statement(env, &econ.creator, period, &st);
+
+    if let Some(co) = &econ.co_creator {
+        let mut st = statement(env, co, period);
+        st.royalty_earned = st
+            .royalty_earned
+            .checked_add(econ.co_creator_share)
+            .ok_or(Error::Overflow)?;
+        if seller == co {
+            st.primary_sales = st
+                .primary_sales
+                .checked_add(econ.seller_net)
+                .ok_or(Error::Overflow)?;
+        }
+        st.per_token_breakdown_count = st.per_token_breakdown_count.saturating_add(1);
+        set_statement(env, co, period, &st);
+    }
+    Ok(())
+}
+
+// Lưu bằng chứng royalty đã trả cho token và cộng dồn tổng royalty trọn đời
+fn record_royalty_payment(
+    env: &Env,
+    id: u128,
+    buyer: &Address,
+    econ: &SaleEconomics,
+) -> Result<(), Error> {
+    if econ.royalty <= 0 {
+        return Ok(());
+    }
+    env.storage().instance().set::<MarketKey, RoyaltyPayment>(
+        &MarketKey::LastRoyaltyPayment(id),
+        &RoyaltyPayment {
+            buyer: buyer.clone(),
+            amount: econ.royalty,
+            paid_to: econ.creator.clone(),
+            at_ledger: env.ledger().sequence() as u64,
+        },
+    );
+    let total: i128 = env
+        .storage()
+        .instance()
+        .get::<MarketKey, i128>(&MarketKey::TotalRoyaltyPaid(id))
+        .unwrap_or(0);
+    let total = total.checked_add(econ.royalty).ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set::<MarketKey, i128>(&MarketKey::TotalRoyaltyPaid(id), &total);
+    Ok(())
+}
+
+// Tổng số mục đã ghi từ trước tới nay, kể cả mục đã bị ghi đè
+fn admin_log_len(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get::<DataKey, u64>(&DataKey::AdminLogCount)
+        .unwrap_or(0)
+}
+
+// Ghi nhật ký thao tác admin. Chỉ gọi sau khi thao tác đã thành công.
+fn log_admin_action(env: &Env, action: Symbol, actor: &Address, subject: LogSubject) {
+    let count = admin_log_len(env);
+    let slot = (count % ADMIN_LOG_CAP as u64) as u32;
+    env.storage().persistent().set::<DataKey, AdminLogEntry>(
+        &DataKey::AdminLog(slot),
+        &AdminLogEntry {
+            action,
+            actor: actor.clone(),
+            subject,
+            ledger: env.ledger().sequence(),
+        },
+    );
+    env.storage()
+        .instance()
+        .set::<DataKey, u64>(&DataKey::AdminLogCount, &count.saturating_add(1));
+}
+
+// Creator có lịch mint thì chỉ được mint trong một cửa sổ [start, end]
+fn check_mint_window(env: &Env, creator: &Address) -> Result<(), Error> {
+    let exempt = env
+        .storage()
+        .instance()
+        .get::<DataKey, bool>(&DataKey::WindowExempt(creator.clone()))
+        .unwrap_or(false);
+    if exempt {
+        return Ok(());
+    }
+    let windows = match env
+        .storage()
+        .instance()
+        .get::<DataKey, Vec<MintWindow>>(&DataKey::MintingWindow(creator.clone()))
+    {
+        Some(w) if !w.is_empty() => w,
+        _ => return Ok(()),
+    };
+    let now = env.ledger().timestamp();
+    if windows.iter().any(|w| w.start <= now && now <= w.end) {
+        Ok(())
+    } else {
+        Err(Error::OutsideMintWindow)
+    }
+}
+
+fn mint_token(
+    env: &Env,
+    creator: &Address,
+    initial_owner: &Address,
+    royalty_bps: u32,
+    uri: &Bytes,
+) -> Result<u128, Error> {
+    if royalty_bps > 10_000 {
+        return Err(Error::InvalidRoyalty);
+    }
+
+    let id = next_id(env)?;
+
+    set_owner(env, id, initial_owner);
+    set_creator(env, id, creator);
+    index_creator_token(env, creator, id);
+    set_royalty_bps(env, id, royalty_bps);
+    set_uri(env, id, uri);
+    let minted_at = env.ledger().timestamp();
+    env.storage()
+        .instance()
+        .set::<DataKey, u64>(&DataKey::MintTimestamp(id), &minted_at);
+
+    env.storage()
+        .instance()
+        .set::<DataKey, Address>(&DataKey::MintedTo(id), initial_owner);
+    if initial_owner != creator {
+        record_acquisition(env, id);
+    }
+    let hash = provenance_hash(env, creator, initial_owner, royalty_bps, uri, minted_at);
+    env.storage()
+        .instance()
+        .set::<DataKey, BytesN<32>>(&DataKey::ProvenanceHash(id), &hash);
+
+    Ok(id)
+}
+
+// sha256(creator || owner || royalty_bps || uri || mint_timestamp), Address ở dạng XDR,
+// số nguyên big-endian. Tính một lần lúc mint, không cập nhật về sau.
+fn provenance_hash(
+    env: &Env,
+    creator: &Address,
+    owner: &Address,
+    royalty_bps: u32,
+    uri: &Bytes,
+    minted_at: u64,
+) -> BytesN<32> {
+    let mut data = creator.clone().to_xdr(env);
+    data.append(&owner.clone().to_xdr(env));
+    data.extend_from_array(&royalty_bps.to_be_bytes());
+    data.append(uri);
+    data.extend_from_array(&minted_at.to_be_bytes());
+    env.crypto().sha256(&data).into()
+}
+
 fn next_id(env: &Env) -> Result<u128, Error> {
     let current: u128 = env
         .storage()
@@ -233,7 +5141,10 @@ fn set_creator(env: &Env, id: u128, creator: &Address) {
         .set::<DataKey, Address>(&DataKey::Creator(id), creator);
 }
 fn get_creator(env: &Env, id: u128) -> Option<Address> {
-    env.storage().instance().get::<DataKey, Address>(&DataKey::Creator(id))
+    env.storage()
+        .instance()
+        .get::<DataKey, Address>(&DataKey::Creator(id))
+        .map(|c| resolve_creator(env, c))
 }
 fn set_royalty_bps(env: &Env, id: u128, bps: u32) {
     env.storage()
@@ -251,25 +5162,647 @@ fn set_uri(env: &Env, id: u128, uri: &Bytes) {
 fn get_uri(env: &Env, id: u128) -> Option<Bytes> {
     env.storage().instance().get::<DataKey, Bytes>(&DataKey::Uri(id))
 }
+fn require_owner(env: &Env, id: u128, owner: &Address) -> Result<(), Error> {
+    let current = get_owner(env, id).ok_or(Error::TokenNotFound)?;
+    if current != *owner {
+        return Err(Error::NotOwner);
+    }
+    owner.require_auth();
+    Ok(())
+}
+// Caller phải là creator hoặc co-creator, và mọi creator của token đều phải ký
+fn require_all_creators(env: &Env, id: u128, caller: &Address) -> Result<(), Error> {
+    let creator = get_creator(env, id).ok_or(Error::TokenNotFound)?;
+    let co_creator = get_co_creator(env, id).map(|c| c.co_creator);
+    if *caller != creator && Some(caller.clone()) != co_creator {
+        return Err(Error::NotAuthorized);
+    }
+    creator.require_auth();
+    if let Some(co) = co_creator {
+        co.require_auth();
+    }
+    Ok(())
+}
+// Creator chính hoặc co-creator đều được dùng các hàm chỉ dành cho creator
+fn require_creator(env: &Env, id: u128, creator: &Address) -> Result<(), Error> {
+    let primary = get_creator(env, id).ok_or(Error::TokenNotFound)?;
+    let is_co = get_co_creator(env, id).is_some_and(|c| c.co_creator == *creator);
+    if primary != *creator && !is_co {
+        return Err(Error::NotAuthorized);
+    }
+    creator.require_auth();
+    Ok(())
+}
+fn form_required(env: &Env, id: u128) -> bool {
+    env.storage()
+        .instance()
+        .get::<DataKey, PurchaseForm>(&DataKey::PurchaseForm(id))
+        .is_some_and(|f| f.required)
+}
+fn token_locked(env: &Env, id: u128) -> bool {
+    env.storage()
+        .instance()
+        .get::<MarketKey, bool>(&MarketKey::Locked(id))
+        .unwrap_or(false)
+}
+fn set_locked(env: &Env, id: u128, locked: bool) {
+    if locked {
+        env.storage()
+            .instance()
+            .set::<MarketKey, bool>(&MarketKey::Locked(id), &true);
+    } else {
+        env.storage().instance().remove(&MarketKey::Locked(id));
+    }
+}
+fn retired(env: &Env, id: u128) -> bool {
+    env.storage()
+        .instance()
+        .get::<MarketKey, bool>(&MarketKey::Retired(id))
+        .unwrap_or(false)
+}
+fn metadata_frozen(env: &Env, id: u128) -> bool {
+    env.storage()
+        .instance()
+        .get::<DataKey, bool>(&DataKey::MetadataFrozen(id))
+        .unwrap_or(false)
+}
+fn ensure_not_frozen(env: &Env, id: u128) -> Result<(), Error> {
+    if metadata_frozen(env, id) {
+        return Err(Error::MetadataFrozen);
+    }
+    Ok(())
+}
+// Token theo creator, gồm cả catalogue nhận qua transfer_creatorship; chỉ thêm vào cuối
+fn creator_tokens(env: &Env, creator: &Address) -> Vec<u128> {
+    env.storage()
+        .instance()
+        .get::<MarketKey, Vec<u128>>(&MarketKey::CreatorTokens(creator.clone()))
+        .unwrap_or(Vec::new(env))
+}
+// Token collab nằm trong index của cả hai creator
+fn index_creator_token(env: &Env, creator: &Address, id: u128) {
+    let mut tokens = creator_tokens(env, creator);
+    if tokens.contains(id) {
+        return;
+    }
+    tokens.push_back(id);
+    env.storage()
+        .instance()
+        .set::<MarketKey, Vec<u128>>(&MarketKey::CreatorTokens(creator.clone()), &tokens);
+}
+fn promos_of(env: &Env, creator: &Address) -> Vec<Promo> {
+    env.storage()
+        .instance()
+        .get::<MarketKey, Vec<Promo>>(&MarketKey::Promos(creator.clone()))
+        .unwrap_or(Vec::new(env))
+}
+// Áp khuyến mãi đang chạy của creator lên giá: (giá mới, bps đã áp, bị bỏ qua).
+// Nếu giá sau giảm thấp hơn phí tối thiểu creator đặt thì giữ giá gốc.
+fn promo_price(env: &Env, id: u128, price: i128) -> Result<(i128, u32, bool), Error> {
+    let creator = get_creator(env, id).ok_or(Error::TokenNotFound)?;
+    let ledger = env.ledger().sequence();
+    let Some(promo) = promos_of(env, &creator)
+        .iter()
+        .find(|p| p.start_ledger <= ledger && ledger <= p.end_ledger)
+    else {
+        return Ok((price, 0, false));
+    };
+    let discount =
+        safe_mul_div(price, promo.discount_bps as i128, 10_000).ok_or(Error::Overflow)?;
+    let discounted = price.checked_sub(discount).ok_or(Error::Overflow)?;
+    let min_fee: i128 = env
+        .storage()
+        .instance()
+        .get::<MarketKey, i128>(&MarketKey::CreatorMinListingFee(creator))
+        .unwrap_or(0);
+    if discounted <= 0 || discounted < min_fee {
+        return Ok((price, 0, true));
+    }
+    Ok((discounted, promo.discount_bps, false))
+}
+fn member_tokens(env: &Env, member: &Address) -> Vec<u128> {
+    env.storage()
+        .instance()
+        .get::<MarketKey, Vec<u128>>(&MarketKey::MemberTokens(member.clone()))
+        .unwrap_or(Vec::new(env))
+}
+// Quyền lợi của hạng cao nhất còn hạn mà member vẫn đang giữ
+fn membership_benefits(env: &Env, member: &Address) -> Option<Benefits> {
+    let now = env.ledger().timestamp();
+    let mut best: Option<Membership> = None;
+    for id in member_tokens(env, member).iter() {
+        if get_owner(env, id).as_ref() != Some(member) {
+            continue;
+        }
+        let Some(m) = env
+            .storage()
+            .instance()
+            .get::<DataKey, Membership>(&DataKey::MembershipBenefits(id))
+        else {
+            continue;
+        };
+        if now > m.expires_at {
+            continue;
+        }
+        let higher = match &best {
+            Some(b) => m.tier_level > b.tier_level,
+            None => true,
+        };
+        if higher {
+            best = Some(m);
+        }
+    }
+    best.map(|m| m.benefits)
+}
+// Giá sau giảm của member, không thấp hơn phí tối thiểu của creator
+fn member_price(env: &Env, id: u128, buyer: &Address, price: i128) -> Result<i128, Error> {
+    let Some(benefits) = membership_benefits(env, buyer) else {
+        return Ok(price);
+    };
+    let discount =
+        safe_mul_div(price, benefits.discount_bps as i128, 10_000).ok_or(Error::Overflow)?;
+    let discounted = price.checked_sub(discount).ok_or(Error::Overflow)?;
+    let creator = get_creator(env, id).ok_or(Error::TokenNotFound)?;
+    let min_fee: i128 = env
+        .storage()
+        .instance()
+        .get::<MarketKey, i128>(&MarketKey::CreatorMinListingFee(creator))
+        .unwrap_or(0);
+    Ok(discounted.max(min_fee).max(1).min(price))
+}
+// Đặt lại hạn độc quyền theo creator khi token đổi người giữ
+fn record_acquisition(env: &Env, id: u128) {
+    let now = env.ledger().timestamp();
+    let period: u64 = get_creator(env, id)
+        .and_then(|c| {
+            env.storage()
+                .instance()
+                .get::<MarketKey, u64>(&MarketKey::ExclusivityPeriod(c))
+        })
+        .unwrap_or(0);
+    if period == 0 {
+        env.storage()
+            .instance()
+            .remove(&MarketKey::ExclusivityEnd(id));
+    } else {
+        env.storage()
+            .instance()
+            .set::<MarketKey, u64>(&MarketKey::ExclusivityEnd(id), &now.saturating_add(period));
+    }
+}
+fn ensure_exclusivity_over(env: &Env, id: u128) -> Result<(), Error> {
+    let end = env
+        .storage()
+        .instance()
+        .get::<MarketKey, u64>(&MarketKey::ExclusivityEnd(id))
+        .unwrap_or(0);
+    if env.ledger().timestamp() < end {
+        return Err(Error::ExclusivityPeriodActive);
+    }
+    Ok(())
+}
+fn ensure_unlocked(env: &Env, id: u128) -> Result<(), Error> {
+    ensure_no_snapshot(env)?;
+    if token_locked(env, id) {
+        return Err(Error::TokenLocked);
+    }
+    Ok(())
+}
+// Trong lúc chụp owner root, mọi thay đổi owner đều bị chặn
+fn ensure_no_snapshot(env: &Env) -> Result<(), Error> {
+    if env
+        .storage()
+        .instance()
+        .get::<MarketKey, bool>(&MarketKey::SnapshotLock)
+        .unwrap_or(false)
+    {
+        return Err(Error::TokenLocked);
+    }
+    Ok(())
+}
+fn token_acl(env: &Env, id: u128) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get::<DataKey, Vec<Address>>(&DataKey::TokenAcl(id))
+        .unwrap_or(Vec::new(env))
+}
+// Điều kiện chung của buy và simulate_buy, trả về owner hiện tại
+fn check_buy_allowed(env: &Env, id: u128, buyer: &Address) -> Result<Address, Error> {
+    let owner = get_owner(env, id).ok_or(Error::TokenNotFound)?;
+    if owner == *buyer {
+        return Err(Error::SameOwner);
+    }
+    check_acl(env, id, buyer)?;
+    ensure_unlocked(env, id)?;
+    ensure_exclusivity_over(env, id)?;
+    Ok(owner)
+}
+// Khi ACL bật, chỉ địa chỉ trong danh sách mới được transfer/buy token
+fn check_acl(env: &Env, id: u128, caller: &Address) -> Result<(), Error> {
+    let enabled = env
+        .storage()
+        .instance()
+        .get::<DataKey, bool>(&DataKey::TokenAclEnabled(id))
+        .unwrap_or(false);
+    if enabled && !token_acl(env, id).contains(caller) {
+        return Err(Error::NotInAcl);
+    }
+    Ok(())
+}
+fn backup_uri(env: &Env, id: u128) -> Option<Bytes> {
+    env.storage()
+        .instance()
+        .get::<DataKey, Bytes>(&DataKey::BackupUri(id))
+}
+// Creator mặc định cho phép mirror, trừ khi đã opt-out
+fn allows_backup(env: &Env, creator: &Address) -> bool {
+    env.storage()
+        .instance()
+        .get::<DataKey, bool>(&DataKey::AllowBackup(creator.clone()))
+        .unwrap_or(true)
+}
+fn uri_langs(env: &Env, id: u128) -> Vec<Symbol> {
+    env.storage()
+        .instance()
+        .get::<DataKey, Vec<Symbol>>(&DataKey::UriLangs(id))
+        .unwrap_or(Vec::new(env))
+}
+fn uri_variants(env: &Env, id: u128) -> Vec<(Symbol, Bytes)> {
+    let mut out: Vec<(Symbol, Bytes)> = Vec::new(env);
+    for lang in uri_langs(env, id).iter() {
+        if let Some(uri) = env
+            .storage()
+            .instance()
+            .get::<DataKey, Bytes>(&DataKey::UriVariant(id, lang.clone()))
+        {
+            out.push_back((lang, uri));
+        }
+    }
+    out
+}
+fn external_links(env: &Env, id: u128) -> Vec<ExternalLink> {
+    env.storage()
+        .instance()
+        .get::<DataKey, Vec<ExternalLink>>(&DataKey::ExternalLinks(id))
+        .unwrap_or(Vec::new(env))
+}
+fn link_platforms(env: &Env) -> Vec<Bytes> {
+    env.storage()
+        .instance()
+        .get::<DataKey, Vec<Bytes>>(&DataKey::LinkPlatforms)
+        .unwrap_or(Vec::new(env))
+}
+fn mint_timestamp(env: &Env, id: u128) -> Option<u64> {
+    env.storage()
+        .instance()
+        .get::<DataKey, u64>(&DataKey::MintTimestamp(id))
+}
+fn set_co_creator(env: &Env, id: u128, info: &CoCreatorInfo) {
+    env.storage()
+        .instance()
+        .set::<DataKey, CoCreatorInfo>(&DataKey::CoCreator(id), info);
+}
+fn get_co_creator(env: &Env, id: u128) -> Option<CoCreatorInfo> {
+    env.storage()
+        .instance()
+        .get::<DataKey, CoCreatorInfo>(&DataKey::CoCreator(id))
+        .map(|mut c| {
+            c.co_creator = resolve_creator(env, c.co_creator);
+            c
+        })
+}
+// (co-creator, phần của creator chính); token không collab thì creator chính nhận đủ
+fn co_creator_split(env: &Env, id: u128) -> (Option<Address>, u32) {
+    match get_co_creator(env, id) {
+        Some(c) => (Some(c.co_creator), c.split_a_bps),
+        None => (None, 10_000),
+    }
+}
+// Creator đã chuyển quyền thì trả về địa chỉ mới (chỉ một bước)
+fn resolve_creator(env: &Env, creator: Address) -> Address {
+    env.storage()
+        .instance()
+        .get::<DataKey, Address>(&DataKey::CreatorRedirect(creator.clone()))
+        .unwrap_or(creator)
+}
+fn both_set<K: IntoVal<Env, Val>>(env: &Env, a: &K, b: &K) -> bool {
+    env.storage().instance().has(a) && env.storage().instance().has(b)
+}
+fn move_setting<K, V>(env: &Env, from: &K, to: &K)
+where
+    K: IntoVal<Env, Val>,
+    V: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    if let Some(value) = env.storage().instance().get::<K, V>(from) {
+        env.storage().instance().set::<K, V>(to, &value);
+        env.storage().instance().remove(from);
+    }
+}
+fn redirect_sources(env: &Env, new: &Address) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get::<DataKey, Vec<Address>>(&DataKey::RedirectSources(new.clone()))
+        .unwrap_or(Vec::new(env))
+}
+fn write_operator_approval(
+    env: &Env,
+    owner: &Address,
+    operator: &Address,
+    approved: bool,
+    until_ledger: u32,
+) {
+    let key = DataKey::OperatorApproval(owner.clone(), operator.clone());
+    if approved {
+        env.storage()
+            .instance()
+            .set::<DataKey, u32>(&key, &until_ledger);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    env.events().publish(
+        (symbol_short!("approval"), owner.clone(), operator.clone()),
+        (approved, until_ledger),
+    );
+}
+// Approval còn hiệu lực đến hết ledger until_ledger, sau đó tự hết hạn.
+// Operator kế nhiệm còn hạn được dùng approval của operator cũ.
+fn is_operator(env: &Env, owner: &Address, operator: &Address) -> bool {
+    if has_operator_approval(env, owner, operator) {
+        return true;
+    }
+    active_operator_successors(env)
+        .iter()
+        .any(|s| s.new_operator == *operator && has_operator_approval(env, owner, &s.old_operator))
+}
+fn has_operator_approval(env: &Env, owner: &Address, operator: &Address) -> bool {
+    match env
+        .storage()
+        .instance()
+        .get::<DataKey, u32>(&DataKey::OperatorApproval(owner.clone(), operator.clone()))
+    {
+        Some(until_ledger) => env.ledger().sequence() <= until_ledger,
+        None => false,
+    }
+}
+fn active_operator_successors(env: &Env) -> Vec<OperatorSuccessor> {
+    let ledger = env.ledger().sequence();
+    let mut active = Vec::new(env);
+    for s in env
+        .storage()
+        .instance()
+        .get::<MarketKey, Vec<OperatorSuccessor>>(&MarketKey::OperatorSuccessors)
+        .unwrap_or(Vec::new(env))
+        .iter()
+    {
+        if ledger <= s.until_ledger {
+            active.push_back(s);
+        }
+    }
+    active
+}
 fn fan_key(addr: &Address) -> DataKey {
     DataKey::FanPoints(addr.clone())
 }
+// Cộng điểm qua các hàm cấp điểm trực tiếp: vượt giới hạn tổng thì báo lỗi
 fn add_fan_points(env: &Env, fan: &Address, points: u128) -> Result<(), Error> {
+    grant_fan_points(env, fan, points, false)
+}
+// Điểm cho người bán theo SellerPointsRate trên số tiền buyer thực trả
+fn seller_points(env: &Env, price: i128) -> Result<u128, Error> {
+    let rate: u32 = env
+        .storage()
+        .instance()
+        .get::<FanKey, u32>(&FanKey::SellerPointsRate)
+        .unwrap_or(0);
+    let points = safe_mul_div(price, rate as i128, 10_000).ok_or(Error::Overflow)?;
+    Ok(points as u128)
+}
+// Điểm thưởng đi kèm mua bán/thanh toán: chạm giới hạn tổng thì cắt bớt (có thể
+// về 0) và phát event, để giao dịch vẫn hoàn tất
+fn add_reward_points(env: &Env, fan: &Address, points: u128) -> Result<(), Error> {
+    grant_fan_points(env, fan, points, true)
+}
+// (điểm hiện có, phần còn lại sau giới hạn mỗi địa chỉ, phần thực sự được cộng
+// sau giới hạn toàn hệ thống). Không ghi gì, simulate_buy dùng chung để báo đúng số.
+fn fan_points_headroom(
+    env: &Env,
+    fan: &Address,
+    points: u128,
+    clip: bool,
+) -> Result<(u128, u128, u128), Error> {
+    let current: u128 = env
+        .storage()
+        .instance()
+        .get::<DataKey, u128>(&fan_key(fan))
+        .unwrap_or(0u128);
+    let mut new_total: u128 = current.checked_add(points).ok_or(Error::Overflow)?;
+
+    // Vượt giới hạn mỗi địa chỉ thì cắt bớt, không báo lỗi
+    let max_points = fan_point_setting(env, FanKey::MaxFanPointsPerAddress);
+    if max_points > 0 && new_total > max_points {
+        new_total = max_points.max(current);
+    }
+
+    let granted = new_total - current;
+    let before = fan_point_setting(env, FanKey::TotalFanPoints);
+    let total = before.checked_add(granted).ok_or(Error::Overflow)?;
+    let max_total = fan_point_setting(env, FanKey::MaxTotalFanPoints);
+    if max_total > 0 && total > max_total {
+        if !clip {
+            return Err(Error::GlobalPointCapReached);
+        }
+        return Ok((current, granted, max_total.saturating_sub(before)));
+    }
+    Ok((current, granted, granted))
+}
+fn grant_fan_points(env: &Env, fan: &Address, points: u128, clip: bool) -> Result<(), Error> {
     if points == 0 {
         return Ok(());
     }
+    let (current, granted, allowed) = fan_points_headroom(env, fan, points, clip)?;
+    if granted < points {
+        env.events()
+            .publish((symbol_short!("pt_cap"), fan.clone()), current + granted);
+    }
+    if allowed < granted {
+        env.events()
+            .publish((symbol_short!("gpt_cap"), fan.clone()), (granted, allowed));
+        if allowed == 0 {
+            return Ok(());
+        }
+    }
+    let total = fan_point_setting(env, FanKey::TotalFanPoints) + allowed;
+    env.storage()
+        .instance()
+        .set::<FanKey, u128>(&FanKey::TotalFanPoints, &total);
+
+    env.storage()
+        .instance()
+        .set::<DataKey, u128>(&fan_key(fan), &(current + allowed));
+    record_fan_join(env, fan);
+    adjust_cohort_points(env, fan, allowed, true);
+    Ok(())
+}
+fn burn_leaderboard(env: &Env) -> Vec<(Address, u128)> {
+    env.storage()
+        .instance()
+        .get::<FanKey, Vec<(Address, u128)>>(&FanKey::BurnLeaderboard)
+        .unwrap_or(Vec::new(env))
+}
+// Đưa fan vào đúng vị trí theo tổng đã đốt (bằng nhau thì ai đạt trước đứng trên)
+fn update_burn_leaderboard(env: &Env, fan: &Address, burned: u128) {
+    let mut board = burn_leaderboard(env);
+    if let Some(i) = board.iter().position(|(addr, _)| addr == *fan) {
+        board.remove(i as u32);
+    }
+    let pos = board
+        .iter()
+        .position(|(_, total)| burned > total)
+        .unwrap_or(board.len() as usize) as u32;
+    if pos >= BURN_LEADERBOARD_SIZE {
+        return;
+    }
+    board.insert(pos, (fan.clone(), burned));
+    while board.len() > BURN_LEADERBOARD_SIZE {
+        board.pop_back();
+    }
+    env.storage()
+        .instance()
+        .set::<FanKey, Vec<(Address, u128)>>(&FanKey::BurnLeaderboard, &board);
+}
+fn granters(env: &Env) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get::<FanKey, Vec<Address>>(&FanKey::Granters)
+        .unwrap_or(Vec::new(env))
+}
+fn grant_key_retention(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get::<FanKey, u32>(&FanKey::GrantKeyRetention)
+        .unwrap_or(0)
+}
+// Key/nonce chống replay nằm trong persistent storage (instance được nạp ở mọi
+// lần gọi); chỉ cần sống hết cửa sổ replay, sau đó lời gọi cũ đã bị từ chối
+// bởi hạn của chính nó.
+fn keep_replay_key<K: IntoVal<Env, Val>>(env: &Env, key: &K, ledgers: u32) {
+    let ttl = ledgers.saturating_add(1).min(env.storage().max_ttl());
+    env.storage().persistent().extend_ttl(key, ttl, ttl);
+}
+// Key còn trong thời gian giữ thì xem như đã dùng
+fn grant_key_used(env: &Env, key: &BytesN<32>) -> bool {
+    let Some(recorded) = env
+        .storage()
+        .persistent()
+        .get::<FanKey, u32>(&FanKey::GrantKey(key.clone()))
+    else {
+        return false;
+    };
+    let retention = grant_key_retention(env);
+    retention == 0 || env.ledger().sequence() <= recorded.saturating_add(retention)
+}
+fn fan_point_setting(env: &Env, key: FanKey) -> u128 {
+    env.storage()
+        .instance()
+        .get::<FanKey, u128>(&key)
+        .unwrap_or(0)
+}
+
+fn endorsement_count(env: &Env, fan: &Address) -> u32 {
+    env.storage()
+        .instance()
+        .get::<DataKey, u32>(&DataKey::EndorsementCount(fan.clone()))
+        .unwrap_or(0)
+}
+// Thưởng thêm min(số lượt endorse, 10) * EndorseMultiplierBps trên số điểm được cấp
+fn endorsement_bonus(env: &Env, fan: &Address, points: u128) -> Result<u128, Error> {
+    let multiplier_bps: u32 = env
+        .storage()
+        .instance()
+        .get::<DataKey, u32>(&DataKey::EndorseMultiplierBps)
+        .unwrap_or(0);
+    let count = endorsement_count(env, fan).min(10);
+    let bonus_bps = (count as u128)
+        .checked_mul(multiplier_bps as u128)
+        .ok_or(Error::Overflow)?;
+    let bonus = points.checked_mul(bonus_bps).ok_or(Error::Overflow)?;
+    Ok(bonus / 10_000)
+}
+fn spend_fan_points(env: &Env, fan: &Address, points: u128) -> Result<(), Error> {
     let current: u128 = env
         .storage()
         .instance()
         .get::<DataKey, u128>(&fan_key(fan))
         .unwrap_or(0u128);
-    let new_total: u128 = current.checked_add(points).ok_or(Error::Overflow)?;
+    let new_total = current
+        .checked_sub(points)
+        .ok_or(Error::InsufficientPoints)?;
     env.storage()
         .instance()
         .set::<DataKey, u128>(&fan_key(fan), &new_total);
+    // Điểm có trước khi bắt đầu đếm tổng không nằm trong TotalFanPoints
+    let total = fan_point_setting(env, FanKey::TotalFanPoints).saturating_sub(points);
+    env.storage()
+        .instance()
+        .set::<FanKey, u128>(&FanKey::TotalFanPoints, &total);
+    adjust_cohort_points(env, fan, points, false);
     Ok(())
 }
 
+// Lần đầu fan nhận điểm: ghi ledger tham gia và thêm vào cohort tuần đó
+fn record_fan_join(env: &Env, fan: &Address) {
+    let join_key = DataKey::FanJoinLedger(fan.clone());
+    if env.storage().instance().has(&join_key) {
+        return;
+    }
+    let ledger = env.ledger().sequence();
+    env.storage()
+        .instance()
+        .set::<DataKey, u32>(&join_key, &ledger);
+
+    let week = ledger / LEDGERS_PER_WEEK;
+    let size = cohort_size(env, week);
+    let page = size / COHORT_PAGE_SIZE;
+    let mut members = cohort_page(env, week, page);
+    members.push_back(fan.clone());
+    env.storage()
+        .persistent()
+        .set::<DataKey, Vec<Address>>(&DataKey::CohortIndex(week, page), &members);
+    env.storage()
+        .instance()
+        .set::<FanKey, u32>(&FanKey::CohortSize(week), &size.saturating_add(1));
+}
+// Giữ tổng điểm theo cohort tuần tham gia của fan, để thống kê không phải duyệt cả cohort
+fn adjust_cohort_points(env: &Env, fan: &Address, points: u128, add: bool) {
+    let Some(ledger) = env
+        .storage()
+        .instance()
+        .get::<DataKey, u32>(&DataKey::FanJoinLedger(fan.clone()))
+    else {
+        return;
+    };
+    let key = FanKey::CohortPoints(ledger / LEDGERS_PER_WEEK);
+    let current = fan_point_setting(env, key.clone());
+    let total = if add {
+        current.saturating_add(points)
+    } else {
+        current.saturating_sub(points)
+    };
+    env.storage().instance().set::<FanKey, u128>(&key, &total);
+}
+fn cohort_size(env: &Env, week: u32) -> u32 {
+    env.storage()
+        .instance()
+        .get::<FanKey, u32>(&FanKey::CohortSize(week))
+        .unwrap_or(0)
+}
+fn cohort_page(env: &Env, week: u32, page: u32) -> Vec<Address> {
+    env.storage()
+        .persistent()
+        .get::<DataKey, Vec<Address>>(&DataKey::CohortIndex(week, page))
+        .unwrap_or(Vec::new(env))
+}
+
 // Hàm nhân–chia an toàn
 // Hàm nhân–chia an toàn, tránh tràn số
 fn safe_mul_div(a: i128, b: i128, c: i128) -> Option<i128> {
@@ -280,6 +5813,81 @@ fn safe_mul_div(a: i128, b: i128, c: i128) -> Option<i128> {
     prod.checked_div(c)
 }
 
+// Hash cam kết cho vé export, tính trên mọi trường trừ commitment
+fn export_commitment(env: &Env, ticket: &ExportTicket) -> BytesN<32> {
+    let fields = (
+        ticket.source_contract.clone(),
+        ticket.destination_contract.clone(),
+        ticket.token_id,
+        ticket.owner.clone(),
+        ticket.creator.clone(),
+        ticket.co_creator.clone(),
+        ticket.split_a_bps,
+        ticket.royalty_bps,
+        ticket.uri.clone(),
+        ticket.uri_variants.clone(),
+        ticket.metadata_frozen,
+        ticket.retired,
+    );
+    env.crypto().sha256(&fields.to_xdr(env)).into()
+}
+
+fn shared_action_hash(
+    env: &Env,
+    id: u128,
+    action: Symbol,
+    counterparty: &Address,
+    price: i128,
+) -> BytesN<32> {
+    let fields = (action, id, counterparty.clone(), price);
+    env.crypto().sha256(&fields.to_xdr(env)).into()
+}
+fn action_approvals(env: &Env, id: u128, action_hash: &BytesN<32>) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get::<MarketKey, Vec<Address>>(&MarketKey::ActionApprovals(id, action_hash.clone()))
+        .unwrap_or(Vec::new(env))
+}
+// Tổng cổ phần của các holder đã duyệt action_hash phải vượt threshold
+fn require_shared_approval(
+    env: &Env,
+    id: u128,
+    action_hash: &BytesN<32>,
+) -> Result<SharedControl, Error> {
+    let control = env
+        .storage()
+        .instance()
+        .get::<MarketKey, SharedControl>(&MarketKey::SharedControl(id))
+        .ok_or(Error::NotActive)?;
+    let approvals = action_approvals(env, id, action_hash);
+    let mut approved: u32 = 0;
+    for (holder, share) in control.holders.iter() {
+        if approvals.contains(&holder) {
+            approved += share;
+        }
+    }
+    if approved <= control.threshold_bps {
+        return Err(Error::InsufficientApprovals);
+    }
+    Ok(control)
+}
+fn end_shared_control(env: &Env, id: u128) {
+    let key = MarketKey::SharedControl(id);
+    if let Some(control) = env
+        .storage()
+        .instance()
+        .get::<MarketKey, SharedControl>(&key)
+    {
+        for hash in control.pending.iter() {
+            env.storage()
+                .instance()
+                .remove(&MarketKey::ActionApprovals(id, hash));
+        }
+    }
+    env.storage().instance().remove(&key);
+    set_locked(env, id, false);
+}
+
 // Hàm gọi cross-contract tới token chuẩn để chuyển tiền
 fn token_transfer_from(
     env: &Env,
@@ -300,6 +5908,25 @@ fn token_transfer_from(
     args.push_back(amount.into_val(env));
 
     // invoke_contract trả về trực tiếp (), nếu lỗi sẽ panic
-    env.invoke_contract::<()>(&token, &func, args);
+    env.invoke_contract::<()>(token, &func, args);
+    Ok(())
+}
+
+// Chuyển token đang giữ trong contract (escrow) ra ngoài
+fn token_transfer(env: &Env, token: &Address, to: &Address, amount: i128) -> Result<(), Error> {
+    if amount <= 0 {
+        return Ok(());
+    }
+    let func = symbol_short!("transfer");
+
+    let mut args: Vec<Val> = Vec::new(env);
+    args.push_back(env.current_contract_address().into_val(env));
+    args.push_back(to.into_val(env));
+    args.push_back(amount.into_val(env));
+
+    env.invoke_contract::<()>(token, &func, args);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test;
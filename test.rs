@@ -0,0 +1,3424 @@
+#![cfg(test)]
+
+extern crate std;
+
+use super::*;
+use ed25519_dalek::{Signer, SigningKey};
+use soroban_sdk::testutils::storage::Persistent as _;
+use soroban_sdk::testutils::Events;
+use soroban_sdk::TryFromVal;
+use soroban_sdk::{
+    contract, contractimpl, symbol_short,
+    testutils::{Address as _, Ledger},
+    Address, Bytes, Env, Symbol, Vec,
+};
+
+// ===========================
+// Token thanh toán giả lập
+// ===========================
+//
+// Contract gọi token qua "xfer_from"(from, to, amount) và
+// "transfer"(from, to, amount), nên token giả chỉ cần hai hàm đó.
+
+#[contract]
+pub struct MockToken;
+
+#[contractimpl]
+impl MockToken {
+    pub fn mint(env: Env, to: Address, amount: i128) {
+        let balance = Self::balance(env.clone(), to.clone());
+        env.storage().instance().set(&to, &(balance + amount));
+    }
+
+    pub fn balance(env: Env, id: Address) -> i128 {
+        env.storage().instance().get(&id).unwrap_or(0)
+    }
+
+    pub fn xfer_from(env: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+        move_balance(&env, &from, &to, amount);
+    }
+
+    pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+        move_balance(&env, &from, &to, amount);
+    }
+}
+
+fn move_balance(env: &Env, from: &Address, to: &Address, amount: i128) {
+    let from_balance: i128 = env.storage().instance().get(from).unwrap_or(0);
+    if from_balance < amount {
+        panic!("insufficient balance");
+    }
+    env.storage().instance().set(from, &(from_balance - amount));
+    let to_balance: i128 = env.storage().instance().get(to).unwrap_or(0);
+    env.storage().instance().set(to, &(to_balance + amount));
+}
+
+// ===========================
+// Helpers
+// ===========================
+
+const ADMIN_TERM: u64 = 1_000_000;
+
+struct Setup<'a> {
+    env: Env,
+    client: FanRewardsNftMarketClient<'a>,
+    admin: Address,
+    token: MockTokenClient<'a>,
+}
+
+fn setup<'a>() -> Setup<'a> {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = env.register_contract(None, FanRewardsNftMarket);
+    let client = FanRewardsNftMarketClient::new(&env, &contract_id);
+    let token_id = env.register_contract(None, MockToken);
+    let token = MockTokenClient::new(&env, &token_id);
+    let admin = Address::generate(&env);
+    client.init(&admin, &ADMIN_TERM);
+    client.set_default_payment_token(&admin, &token_id);
+    Setup {
+        env,
+        client,
+        admin,
+        token,
+    }
+}
+
+// Lỗi contract của một lần gọi try_*
+fn err<T, C, I>(result: Result<Result<T, C>, Result<Error, I>>) -> Error {
+    match result {
+        Err(Ok(e)) => e,
+        Ok(_) => panic!("expected contract error, call succeeded"),
+        Err(Err(_)) => panic!("expected contract error, got host error"),
+    }
+}
+
+fn advance_time(env: &Env, seconds: u64) {
+    env.ledger().with_mut(|l| l.timestamp += seconds);
+}
+
+fn uri(env: &Env, s: &str) -> Bytes {
+    Bytes::from_slice(env, s.as_bytes())
+}
+
+fn mint(s: &Setup, creator: &Address, owner: &Address, royalty_bps: u32) -> u128 {
+    s.client
+        .mint(creator, owner, &royalty_bps, &uri(&s.env, "ipfs://token"))
+        .0
+}
+
+// Ví có sẵn tiền trong token giả
+fn funded(s: &Setup, amount: i128) -> Address {
+    let who = Address::generate(&s.env);
+    s.token.mint(&who, &amount);
+    who
+}
+
+// ===========================
+// Indexer dump (synth-297)
+// ===========================
+
+#[test]
+fn dump_reconstructs_info_ext() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let co = Address::generate(env);
+    let fan = Address::generate(env);
+
+    let platform = uri(env, "x");
+    let mut platforms = Vec::new(env);
+    platforms.push_back(platform.clone());
+    s.client.set_link_platforms(&s.admin, &platforms);
+
+    let plain = mint(&s, &creator, &fan, 500);
+    let collab = s
+        .client
+        .mint_collab(
+            &creator,
+            &co,
+            &fan,
+            &800,
+            &uri(env, "ipfs://collab"),
+            &3_000,
+        )
+        .0;
+    let mut links = Vec::new(env);
+    links.push_back(ExternalLink {
+        platform,
+        url: uri(env, "https://x.com/a"),
+    });
+    s.client
+        .set_external_links(&creator, &TokenId(collab), &links);
+    s.client
+        .set_backup_uri(&s.admin, &TokenId(plain), &uri(env, "ar://mirror"));
+    let buyer = funded(&s, 1_000);
+    s.client.buy(&TokenId(plain), &buyer, &1_000, &None, &None);
+
+    // id 0 và id sau token cuối là khoảng trống
+    let dump = s.client.dump_tokens(&0, &5, &true);
+    assert_eq!(dump.len(), 5);
+    assert!(dump.get(0).unwrap().is_none());
+    assert!(dump.get(3).unwrap().is_none());
+
+    for id in [plain, collab] {
+        let d = dump.get(id as u32).unwrap().unwrap();
+        let ext = s.client.get_info_ext(&TokenId(id));
+        assert_eq!(d.token_id, ext.info.token_id.0);
+        assert_eq!(d.owner, ext.info.owner);
+        assert_eq!(d.creator, ext.info.creator);
+        assert_eq!(d.royalty_bps, ext.info.royalty_bps);
+        assert_eq!(d.uri, Some(ext.info.uri));
+        assert_eq!(d.minted_at, ext.info.minted_at);
+        assert_eq!(d.co_creator, ext.co_creator);
+        assert_eq!(d.split_a_bps, ext.split_a_bps);
+        assert_eq!(d.backup_uri, ext.backup_uri);
+        assert!(d.external_links == ext.external_links);
+    }
+
+    let d = dump.get(plain as u32).unwrap().unwrap();
+    let paid = s.client.get_last_royalty_payment(&TokenId(plain)).unwrap();
+    assert_eq!(d.last_royalty_buyer, Some(paid.buyer));
+    assert_eq!(d.last_royalty_paid_to, Some(paid.paid_to));
+    assert_eq!(d.last_royalty_amount, 50);
+    assert_eq!(d.last_royalty_ledger, paid.at_ledger);
+    assert_eq!(d.total_royalty_paid, 50);
+    assert_eq!(s.token.balance(&creator), 50);
+
+    // Không kèm URI thì các trường nặng để trống
+    let compact = s.client.dump_tokens(&1, &2, &false);
+    let d = compact.get(0).unwrap().unwrap();
+    assert!(d.uri.is_none() && d.backup_uri.is_none() && d.external_links.is_empty());
+}
+
+#[test]
+fn dump_limit_is_bounded() {
+    let s = setup();
+    let dump = s.client.dump_tokens(&1, &(MAX_DUMP_TOKENS + 10), &false);
+    assert_eq!(dump.len(), MAX_DUMP_TOKENS);
+}
+
+// ===========================
+// Admin, hạn admin và hội đồng (synth-265~2)
+// ===========================
+
+#[test]
+fn init_only_once() {
+    let s = setup();
+    let other = Address::generate(&s.env);
+    assert_eq!(
+        err(s.client.try_init(&other, &ADMIN_TERM)),
+        Error::AlreadyActive
+    );
+    assert_eq!(s.client.get_admin(), Some(s.admin.clone()));
+}
+
+#[test]
+fn setters_reject_unstored_admin() {
+    let s = setup();
+    let impostor = Address::generate(&s.env);
+    let token = s.token.address.clone();
+    assert_eq!(
+        err(s.client.try_set_dust_threshold(&impostor, &token, &5)),
+        Error::NotAuthorized
+    );
+    assert_eq!(
+        err(s.client.try_set_snapshot_lock(&impostor, &true)),
+        Error::NotAuthorized
+    );
+    assert_eq!(
+        err(s.client.try_set_default_payment_token(&impostor, &token)),
+        Error::NotAuthorized
+    );
+}
+
+#[test]
+fn admin_expires_and_renews() {
+    let s = setup();
+    let start = s.env.ledger().timestamp();
+    assert_eq!(s.client.get_admin_expiry(), start + ADMIN_TERM);
+
+    // Gia hạn tính từ thời điểm hiện tại
+    advance_time(&s.env, 10);
+    assert_eq!(s.client.renew_admin(&s.admin, &500), start + 10 + 500);
+
+    advance_time(&s.env, 500);
+    s.client.set_claim_points(&s.admin, &1);
+    advance_time(&s.env, 1);
+    assert_eq!(
+        err(s.client.try_set_claim_points(&s.admin, &1)),
+        Error::AdminKeyExpired
+    );
+    assert_eq!(
+        err(s.client.try_renew_admin(&s.admin, &500)),
+        Error::AdminKeyExpired
+    );
+}
+
+#[test]
+fn council_restores_admin_after_expiry() {
+    let s = setup();
+    let env = &s.env;
+    let signers = [
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    ];
+    let mut list = Vec::new(env);
+    for signer in signers.iter() {
+        list.push_back(signer.clone());
+    }
+    s.client.set_council(&s.admin, &list, &2, &100);
+
+    advance_time(env, ADMIN_TERM + 1);
+    let new_admin = Address::generate(env);
+    assert_eq!(
+        err(s
+            .client
+            .try_propose_gov_action(&new_admin, &GovAction::SetAdmin(new_admin.clone(), 1_000))),
+        Error::NotAuthorized
+    );
+    let id = s
+        .client
+        .propose_gov_action(&signers[0], &GovAction::SetAdmin(new_admin.clone(), 1_000));
+    assert_eq!(
+        err(s.client.try_execute_gov_action(&id)),
+        Error::InsufficientApprovals
+    );
+    s.client.approve_gov_action(&signers[1], &id);
+    assert_eq!(
+        err(s.client.try_execute_gov_action(&id)),
+        Error::TimelockActive
+    );
+
+    advance_time(env, 100);
+    s.client.execute_gov_action(&id);
+    assert!(s.client.get_gov_proposal(&id).is_none());
+    assert_eq!(s.client.get_admin(), Some(new_admin.clone()));
+    s.client.set_claim_points(&new_admin, &7);
+    assert_eq!(
+        err(s.client.try_set_claim_points(&s.admin, &7)),
+        Error::NotAuthorized
+    );
+}
+
+#[test]
+fn council_validation() {
+    let s = setup();
+    let env = &s.env;
+    let a = Address::generate(env);
+    let mut dup = Vec::new(env);
+    dup.push_back(a.clone());
+    dup.push_back(a.clone());
+    assert_eq!(
+        err(s.client.try_set_council(&s.admin, &dup, &1, &0)),
+        Error::InvalidSplit
+    );
+    let mut one = Vec::new(env);
+    one.push_back(a.clone());
+    assert_eq!(
+        err(s.client.try_set_council(&s.admin, &one, &2, &0)),
+        Error::InvalidSplit
+    );
+    assert_eq!(
+        err(s.client.try_set_council(&s.admin, &Vec::new(env), &1, &0)),
+        Error::EmptyInput
+    );
+    s.client.set_council(&s.admin, &one, &1, &0);
+
+    // Chỉ người đề xuất mới rút được
+    let id = s
+        .client
+        .propose_gov_action(&a, &GovAction::SetAdmin(a.clone(), 10));
+    let other = Address::generate(env);
+    assert_eq!(
+        err(s.client.try_cancel_gov_action(&other, &id)),
+        Error::NotAuthorized
+    );
+    s.client.cancel_gov_action(&a, &id);
+    assert_eq!(err(s.client.try_execute_gov_action(&id)), Error::NotActive);
+}
+
+// ===========================
+// Tự động hoá giới hạn cho bot (synth-283)
+// ===========================
+
+fn grant(s: &Setup, bot: &Address, setter: &str, min: u128, max: u128) {
+    let mut permissions = Vec::new(&s.env);
+    permissions.push_back(AutomationPermission {
+        setter: Symbol::new(&s.env, setter),
+        min,
+        max,
+    });
+    s.client.grant_automation(&s.admin, bot, &permissions);
+}
+
+#[test]
+fn bot_sets_within_bounds() {
+    let s = setup();
+    let bot = Address::generate(&s.env);
+    grant(&s, &bot, "endorse", 1_000, 2_000);
+
+    s.client.set_endorse_config(&bot, &0, &1_500);
+    let log = s.client.get_admin_log(&0, &ADMIN_LOG_CAP);
+    let last = log.get(log.len() - 1).unwrap();
+    assert_eq!(last.action, symbol_short!("endorse"));
+    assert_eq!(last.actor, bot);
+}
+
+#[test]
+fn bot_rejected_out_of_bounds() {
+    let s = setup();
+    let bot = Address::generate(&s.env);
+    grant(&s, &bot, "endorse", 1_000, 2_000);
+
+    assert_eq!(
+        err(s.client.try_set_endorse_config(&bot, &0, &2_500)),
+        Error::AutomationLimitExceeded
+    );
+    assert_eq!(
+        err(s.client.try_set_endorse_config(&bot, &0, &999)),
+        Error::AutomationLimitExceeded
+    );
+    // min_points vẫn chỉ admin đổi được
+    assert_eq!(
+        err(s.client.try_set_endorse_config(&bot, &5, &1_500)),
+        Error::AutomationLimitExceeded
+    );
+    // Admin không bị giới hạn của bot
+    s.client.set_endorse_config(&s.admin, &5, &3_000);
+}
+
+#[test]
+fn bot_rejected_on_ungranted_setters() {
+    let s = setup();
+    let bot = Address::generate(&s.env);
+    grant(&s, &bot, "claim_pts", 0, 100);
+
+    assert_eq!(
+        err(s.client.try_set_tip_points_divisor(&bot, &10)),
+        Error::NotAuthorized
+    );
+    assert_eq!(
+        err(s.client.try_set_fan_point_caps(&bot, &0, &0)),
+        Error::NotAuthorized
+    );
+    s.client.set_claim_points(&bot, &50);
+
+    s.client.revoke_automation(&s.admin, &bot);
+    assert_eq!(
+        err(s.client.try_set_claim_points(&bot, &50)),
+        Error::NotAuthorized
+    );
+}
+
+#[test]
+fn grant_validation() {
+    let s = setup();
+    let bot = Address::generate(&s.env);
+    let mut unknown = Vec::new(&s.env);
+    unknown.push_back(AutomationPermission {
+        setter: symbol_short!("pt_caps"),
+        min: 0,
+        max: 1,
+    });
+    assert_eq!(
+        err(s.client.try_grant_automation(&s.admin, &bot, &unknown)),
+        Error::UnknownAdminOp
+    );
+    let mut inverted = Vec::new(&s.env);
+    inverted.push_back(AutomationPermission {
+        setter: symbol_short!("tip_div"),
+        min: 5,
+        max: 1,
+    });
+    assert_eq!(
+        err(s.client.try_grant_automation(&s.admin, &bot, &inverted)),
+        Error::InvalidWindow
+    );
+}
+
+// ===========================
+// Token collab hai creator (synth-251~2)
+// ===========================
+
+fn collab(s: &Setup, a: &Address, b: &Address, owner: &Address) -> u128 {
+    s.client
+        .mint_collab(a, b, owner, &800, &uri(&s.env, "ipfs://collab"), &3_000)
+        .0
+}
+
+// Địa chỉ đã ký trong lần gọi contract gần nhất
+fn signed(env: &Env, who: &Address) -> bool {
+    env.auths().iter().any(|(a, _)| a == who)
+}
+
+#[test]
+fn collab_mint_needs_both_and_indexes_both() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = collab(&s, &a, &b, &fan);
+    assert!(signed(env, &a) && signed(env, &b));
+
+    assert!(s.client.get_creator_tokens(&a).contains(id));
+    assert!(s.client.get_creator_tokens(&b).contains(id));
+    let ext = s.client.get_info_ext(&TokenId(id));
+    assert_eq!(ext.co_creator, Some(b.clone()));
+    assert_eq!(ext.split_a_bps, 3_000);
+}
+
+#[test]
+fn collab_sale_splits_royalty() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = collab(&s, &a, &b, &fan);
+    let buyer = funded(&s, 1_000);
+    s.client.buy(&TokenId(id), &buyer, &1_000, &None, &None);
+
+    // Royalty 8% = 80, A nhận 30%
+    assert_eq!(s.token.balance(&a), 24);
+    assert_eq!(s.token.balance(&b), 56);
+    assert_eq!(s.token.balance(&fan), 920);
+}
+
+#[test]
+fn collab_either_vs_both_creator_permissions() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = collab(&s, &a, &b, &fan);
+
+    // Thao tác thường: co-creator tự làm được
+    s.client.set_uri_variant(
+        &b,
+        &TokenId(id),
+        &symbol_short!("vi"),
+        &uri(env, "ipfs://vi"),
+    );
+    assert!(signed(env, &b) && !signed(env, &a));
+
+    // Thao tác không đảo ngược: cần cả hai
+    s.client.waive_royalty_once(&b, &TokenId(id));
+    assert!(signed(env, &a) && signed(env, &b));
+    s.client.retire_token(&a, &TokenId(id));
+    assert!(signed(env, &a) && signed(env, &b));
+
+    let stranger = Address::generate(env);
+    assert_eq!(
+        err(s.client.try_waive_royalty_once(&stranger, &TokenId(id))),
+        Error::NotAuthorized
+    );
+}
+
+#[test]
+fn retire_by_owning_co_creator_needs_both() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b) = (Address::generate(env), Address::generate(env));
+
+    // A vừa là creator vừa giữ token: vẫn phải có chữ ký của B
+    let id = TokenId(collab(&s, &a, &b, &a));
+    s.client.retire_token(&a, &id);
+    assert!(signed(env, &a) && signed(env, &b));
+    assert!(s.client.is_retired(&id));
+    let buyer = funded(&s, 1_000);
+    assert_eq!(
+        err(s.client.try_buy(&id, &buyer, &1_000, &None, &None)),
+        Error::TokenRetired
+    );
+
+    // Owner không phải creator tự retire được
+    let fan = Address::generate(env);
+    let id = TokenId(collab(&s, &a, &b, &fan));
+    assert_eq!(
+        err(s.client.try_retire_token(&Address::generate(env), &id)),
+        Error::NotAuthorized
+    );
+    s.client.retire_token(&fan, &id);
+    assert!(signed(env, &fan) && !signed(env, &a) && !signed(env, &b));
+    assert!(s.client.is_retired(&id));
+}
+
+// ===========================
+// Key ký thao tác admin (synth-252)
+// ===========================
+
+fn admin_key(env: &Env, seed: u8) -> (SigningKey, BytesN<32>) {
+    let key = SigningKey::from_bytes(&[seed; 32]);
+    let public = BytesN::from_array(env, &key.verifying_key().to_bytes());
+    (key, public)
+}
+
+// Chữ ký trên (op_type || params || nonce || contract_id), giống contract
+fn sign_op(s: &Setup, key: &SigningKey, op: &str, params: &Bytes, nonce: u128) -> Bytes {
+    let env = &s.env;
+    let mut message = Bytes::from_slice(env, op.as_bytes());
+    message.append(params);
+    message.extend_from_array(&nonce.to_be_bytes());
+    message.append(&s.client.address.clone().to_xdr(env));
+    let raw: std::vec::Vec<u8> = message.iter().collect();
+    Bytes::from_slice(env, &key.sign(&raw).to_bytes())
+}
+
+#[test]
+fn admin_key_replaced_by_admin() {
+    let s = setup();
+    let (_, first) = admin_key(&s.env, 1);
+    let (_, second) = admin_key(&s.env, 2);
+    s.client.set_admin_public_key(&s.admin, &first);
+    assert_eq!(
+        err(s
+            .client
+            .try_set_admin_public_key(&Address::generate(&s.env), &second)),
+        Error::NotAuthorized
+    );
+    s.client.set_admin_public_key(&s.admin, &second);
+    assert_eq!(s.client.get_admin_public_key(), Some(second));
+}
+
+#[test]
+fn admin_key_dies_with_its_admin() {
+    let s = setup();
+    let env = &s.env;
+    let signers = council(&s);
+    let (old_key, old_public) = admin_key(env, 1);
+    let (new_key, new_public) = admin_key(env, 2);
+    s.client.set_admin_public_key(&s.admin, &old_public);
+
+    let new_admin = Address::generate(env);
+    pass(
+        &s,
+        &signers,
+        GovAction::SetAdmin(new_admin.clone(), ADMIN_TERM),
+    );
+    assert_eq!(s.client.get_admin_public_key(), None);
+
+    let set_param = Bytes::from_slice(env, b"set_param");
+    let kv = (uri(env, "k"), uri(env, "v")).to_xdr(env);
+    let stale = sign_op(&s, &old_key, "set_param", &kv, 1);
+    assert_eq!(
+        err(s
+            .client
+            .try_execute_signed_admin_op(&set_param, &kv, &1, &stale)),
+        Error::NotAuthorized
+    );
+    // Key cũ cũng không tự xoay sang key khác để giữ quyền
+    let rotate = Bytes::from_slice(env, b"rotate_admin_key");
+    let params = old_public.clone().to_xdr(env);
+    let keep = sign_op(&s, &old_key, "rotate_admin_key", &params, 2);
+    assert_eq!(
+        err(s
+            .client
+            .try_execute_signed_admin_op(&rotate, &params, &2, &keep)),
+        Error::NotAuthorized
+    );
+
+    s.client.set_admin_public_key(&new_admin, &new_public);
+    let fresh = sign_op(&s, &new_key, "set_param", &kv, 1);
+    s.client
+        .execute_signed_admin_op(&set_param, &kv, &1, &fresh);
+    assert_eq!(s.client.get_param(&uri(env, "k")), Some(uri(env, "v")));
+}
+
+#[test]
+fn admin_key_rotates_only_with_current_signature() {
+    let s = setup();
+    let env = &s.env;
+    let (old_key, old_public) = admin_key(env, 1);
+    let (new_key, new_public) = admin_key(env, 2);
+    s.client.set_admin_public_key(&s.admin, &old_public);
+
+    let op = Bytes::from_slice(env, b"rotate_admin_key");
+    let params = new_public.clone().to_xdr(env);
+    // Key mới tự ký cho mình thì bị từ chối
+    let forged = sign_op(&s, &new_key, "rotate_admin_key", &params, 1);
+    assert!(s
+        .client
+        .try_execute_signed_admin_op(&op, &params, &1, &forged)
+        .is_err());
+    assert_eq!(s.client.get_admin_public_key(), Some(old_public));
+
+    let signature = sign_op(&s, &old_key, "rotate_admin_key", &params, 2);
+    s.client
+        .execute_signed_admin_op(&op, &params, &2, &signature);
+    assert_eq!(s.client.get_admin_public_key(), Some(new_public));
+
+    // Key cũ hết hiệu lực
+    let set_param = Bytes::from_slice(env, b"set_param");
+    let kv = (uri(env, "k"), uri(env, "v")).to_xdr(env);
+    let stale = sign_op(&s, &old_key, "set_param", &kv, 3);
+    assert!(s
+        .client
+        .try_execute_signed_admin_op(&set_param, &kv, &3, &stale)
+        .is_err());
+    let fresh = sign_op(&s, &new_key, "set_param", &kv, 3);
+    s.client
+        .execute_signed_admin_op(&set_param, &kv, &3, &fresh);
+    assert_eq!(s.client.get_param(&uri(env, "k")), Some(uri(env, "v")));
+}
+
+// ===========================
+// Export/import giữa hai contract (synth-252~2)
+// ===========================
+
+// Contract thứ hai cùng mã nguồn, tin contract của `s` làm nguồn
+fn destination<'a>(s: &Setup) -> FanRewardsNftMarketClient<'a> {
+    let id = s.env.register_contract(None, FanRewardsNftMarket);
+    let client = FanRewardsNftMarketClient::new(&s.env, &id);
+    client.init(&s.admin, &ADMIN_TERM);
+    client.set_trusted_source(&s.admin, &s.client.address);
+    client
+}
+
+#[test]
+fn export_import_between_contracts() {
+    let s = setup();
+    let env = &s.env;
+    let dest = destination(&s);
+    let (a, b, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = collab(&s, &a, &b, &fan);
+    s.client.set_uri_variant(
+        &a,
+        &TokenId(id),
+        &symbol_short!("vi"),
+        &uri(env, "ipfs://vi"),
+    );
+    s.client.freeze_metadata(&a, &TokenId(id));
+
+    let ticket = s.client.export_token(&fan, &TokenId(id), &dest.address);
+    assert!(ticket.metadata_frozen && !ticket.retired);
+    // Token gốc đã bị tombstone
+    assert_eq!(
+        err(s.client.try_transfer(&TokenId(id), &fan, &a)),
+        Error::TokenNotFound
+    );
+
+    let new_id = dest.import_token(&ticket, &fan);
+    let info = dest.get_info_ext(&new_id);
+    assert_eq!(info.info.owner, fan);
+    assert_eq!(info.info.creator, a);
+    assert_eq!(info.info.royalty_bps, 800);
+    assert_eq!(info.co_creator, Some(b.clone()));
+    assert_eq!(info.split_a_bps, 3_000);
+    assert!(dest.is_metadata_frozen(&new_id));
+    assert_eq!(
+        dest.get_uri_lang(&new_id, &symbol_short!("vi")),
+        Some(uri(env, "ipfs://vi"))
+    );
+    assert!(dest.get_creator_tokens(&b).contains(new_id.0));
+
+    assert_eq!(
+        err(dest.try_import_token(&ticket, &fan)),
+        Error::AlreadyImported
+    );
+}
+
+#[test]
+fn import_rejects_tampered_or_untrusted_tickets() {
+    let s = setup();
+    let env = &s.env;
+    let dest = destination(&s);
+    let (creator, fan) = (Address::generate(env), Address::generate(env));
+    let id = mint(&s, &creator, &fan, 500);
+    s.client.freeze_metadata(&creator, &TokenId(id));
+    let ticket = s.client.export_token(&fan, &TokenId(id), &dest.address);
+
+    // Sửa cờ sau khi export thì commitment không khớp
+    let mut tampered = ticket.clone();
+    tampered.metadata_frozen = false;
+    assert_eq!(
+        err(dest.try_import_token(&tampered, &fan)),
+        Error::InvalidTicket
+    );
+
+    // Contract không tin nguồn này
+    let other = destination(&s);
+    other.set_trusted_source(&s.admin, &dest.address);
+    assert_eq!(
+        err(other.try_import_token(&ticket, &fan)),
+        Error::UntrustedSource
+    );
+
+    dest.import_token(&ticket, &fan);
+}
+
+// ===========================
+// Approval operator có hạn (synth-253)
+// ===========================
+
+fn set_ledger(env: &Env, sequence: u32) {
+    env.ledger().with_mut(|l| l.sequence_number = sequence);
+}
+
+// Contract vừa phát event có topic đầu là `name`
+fn emitted(env: &Env, name: Symbol) -> bool {
+    env.events().all().iter().any(|(_, topics, _)| {
+        topics
+            .get(0)
+            .and_then(|t| Symbol::try_from_val(env, &t).ok())
+            .is_some_and(|t| t == name)
+    })
+}
+
+#[test]
+fn operator_approval_lapses_after_until_ledger() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, owner, market, buyer) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = mint(&s, &creator, &owner, 0);
+    set_ledger(env, 100);
+    s.client
+        .set_approval_for_all_until(&owner, &market, &true, &110);
+    assert!(!emitted(env, symbol_short!("unbounded")));
+
+    // Ledger 110 vẫn còn hiệu lực, 111 thì hết
+    set_ledger(env, 110);
+    assert!(s.client.is_approved_for_all(&owner, &market));
+    set_ledger(env, 111);
+    assert!(!s.client.is_approved_for_all(&owner, &market));
+    assert_eq!(
+        err(s
+            .client
+            .try_transfer_from(&market, &TokenId(id), &owner, &buyer)),
+        Error::NotAuthorized
+    );
+}
+
+#[test]
+fn operator_approval_renewal_overwrites_expiry() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, owner, market, buyer) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = mint(&s, &creator, &owner, 0);
+    set_ledger(env, 100);
+    s.client
+        .set_approval_for_all_until(&owner, &market, &true, &200);
+    // Gia hạn ngắn lại cũng ghi đè
+    s.client
+        .set_approval_for_all_until(&owner, &market, &true, &150);
+    set_ledger(env, 151);
+    assert!(!s.client.is_approved_for_all(&owner, &market));
+
+    s.client
+        .set_approval_for_all_until(&owner, &market, &true, &300);
+    s.client
+        .transfer_from(&market, &TokenId(id), &owner, &buyer);
+    assert_eq!(s.client.get_info(&TokenId(id)).owner, buyer);
+}
+
+#[test]
+fn unbounded_approval_warns_and_can_be_revoked() {
+    let s = setup();
+    let env = &s.env;
+    let (owner, market) = (Address::generate(env), Address::generate(env));
+    s.client.set_approval_for_all(&owner, &market, &true);
+    assert!(emitted(env, symbol_short!("unbounded")));
+    set_ledger(env, 4_000);
+    assert!(s.client.is_approved_for_all(&owner, &market));
+
+    s.client.set_approval_for_all(&owner, &market, &false);
+    assert!(!s.client.is_approved_for_all(&owner, &market));
+}
+
+// ===========================
+// Mọi đường bán cho cùng kết quả (synth-266~2)
+// ===========================
+
+#[test]
+fn every_settlement_path_agrees() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b) = (Address::generate(env), Address::generate(env));
+    set_ledger(env, 100);
+    // Giá 1_000: khuyến mãi 20% còn 800, thành viên giảm 25% còn 600.
+    // Royalty 8% = 48 được nâng lên phí tối thiểu 100; phần của A (30) là dust.
+    s.client.schedule_promo(&a, &2_000, &100, &200);
+    s.client.set_min_listing_fee(&a, &100);
+    s.client.set_dust_threshold(&s.admin, &s.token.address, &35);
+    s.client.set_seller_points_rate(&s.admin, &1_000);
+    let expires = env.ledger().timestamp() + 1_000;
+
+    // (A, B, seller, số tiền buyer trả, điểm buyer, điểm seller)
+    let mut outcomes = std::vec::Vec::new();
+    for path in 0..3 {
+        let seller = Address::generate(env);
+        let buyer = funded(&s, 1_000);
+        s.client.mint_membership(
+            &s.admin,
+            &buyer,
+            &1,
+            &benefits(2_500),
+            &expires,
+            &uri(env, "ipfs://member"),
+        );
+        let id = collab(&s, &a, &b, &seller);
+        let (a_before, b_before) = (s.token.balance(&a), s.token.balance(&b));
+        match path {
+            0 => s.client.buy(&TokenId(id), &buyer, &1_000, &None, &None),
+            1 => {
+                let quote = s
+                    .client
+                    .simulate_buy(&TokenId(id), &buyer, &1_000, &None, &None);
+                s.client.buy(&TokenId(id), &buyer, &1_000, &None, &None);
+                let royalty = s.token.balance(&a) - a_before + s.token.balance(&b) - b_before;
+                assert_eq!(quote.royalty, royalty);
+                assert_eq!(quote.seller_amount, s.token.balance(&seller));
+                assert_eq!(quote.effective_price, 1_000 - s.token.balance(&buyer));
+                assert_eq!(quote.points_awarded, s.client.get_fan_points(&buyer));
+                assert_eq!(quote.promo_bps, 2_000);
+            }
+            _ => {
+                let bundle = s.client.propose_cross_bundle(
+                    &seller,
+                    &Vec::from_array(env, [id]),
+                    &1_000,
+                    &s.token.address,
+                );
+                s.client.approve_cross_bundle(&a, &bundle);
+                s.client.approve_cross_bundle(&b, &bundle);
+                s.client.buy_cross_bundle(&buyer, &bundle);
+            }
+        }
+        assert_eq!(s.client.get_info(&TokenId(id)).owner, buyer);
+        outcomes.push((
+            s.token.balance(&a) - a_before,
+            s.token.balance(&b) - b_before,
+            s.token.balance(&seller),
+            1_000 - s.token.balance(&buyer),
+            s.client.get_fan_points(&buyer),
+            s.client.get_fan_points(&seller),
+        ));
+    }
+
+    assert_eq!(outcomes[0], (0, 70, 530, 600, 600, 60));
+    assert_eq!(outcomes[1], outcomes[0]);
+    assert_eq!(outcomes[2], outcomes[0]);
+}
+
+// ===========================
+// simulate_buy kiểm tra như buy (synth-253~2)
+// ===========================
+
+#[test]
+fn simulate_buy_matches_buy_outcome() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, seller) = (Address::generate(env), Address::generate(env));
+    let id = mint(&s, &creator, &seller, 500);
+    let buyer = funded(&s, 1_000);
+
+    let quote = s
+        .client
+        .simulate_buy(&TokenId(id), &buyer, &1_000, &None, &None);
+    assert!(!quote.form_required);
+    s.client.buy(&TokenId(id), &buyer, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&creator), quote.royalty);
+    assert_eq!(s.token.balance(&seller), quote.seller_amount);
+    assert_eq!(s.client.get_fan_points(&buyer), quote.points_awarded);
+}
+
+#[test]
+fn simulate_buy_clips_points_like_buy() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    s.client.set_fan_point_caps(&s.admin, &800, &1_500);
+    let buyer = funded(&s, 2_000);
+
+    // Giới hạn mỗi địa chỉ
+    let first = TokenId(mint(&s, &creator, &creator, 0));
+    let quote = s.client.simulate_buy(&first, &buyer, &1_000, &None, &None);
+    assert_eq!(quote.points_awarded, 800);
+    s.client.buy(&first, &buyer, &1_000, &None, &None);
+    assert_eq!(s.client.get_fan_points(&buyer), quote.points_awarded);
+
+    // Giới hạn toàn hệ thống còn 700
+    let other = funded(&s, 1_000);
+    let second = TokenId(mint(&s, &creator, &creator, 0));
+    let quote = s.client.simulate_buy(&second, &other, &1_000, &None, &None);
+    assert_eq!(quote.points_awarded, 700);
+    s.client.buy(&second, &other, &1_000, &None, &None);
+    assert_eq!(s.client.get_fan_points(&other), quote.points_awarded);
+}
+
+#[test]
+fn simulate_buy_fails_where_buy_fails() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, seller, outsider) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = mint(&s, &creator, &seller, 500);
+    let quote = |who: &Address| {
+        err(s
+            .client
+            .try_simulate_buy(&TokenId(id), who, &1_000, &None, &None))
+    };
+
+    // ACL
+    let mut allowed = Vec::new(env);
+    allowed.push_back(seller.clone());
+    s.client
+        .set_token_acl(&seller, &TokenId(id), &allowed, &true);
+    assert_eq!(quote(&outsider), Error::NotInAcl);
+    s.client
+        .set_token_acl(&seller, &TokenId(id), &Vec::new(env), &false);
+
+    // Khoá snapshot
+    s.client.set_snapshot_lock(&s.admin, &true);
+    assert_eq!(quote(&outsider), Error::TokenLocked);
+    s.client.set_snapshot_lock(&s.admin, &false);
+
+    // Thời gian độc quyền sau lần mua trước
+    s.client.set_exclusivity_period(&creator, &100);
+    let first = funded(&s, 1_000);
+    s.client.buy(&TokenId(id), &first, &1_000, &None, &None);
+    assert_eq!(quote(&outsider), Error::ExclusivityPeriodActive);
+    advance_time(env, 100);
+
+    // Entrypoint buy bị tắt
+    s.client
+        .set_entrypoint_enabled(&s.admin, &symbol_short!("buy"), &false);
+    assert_eq!(quote(&outsider), Error::EntrypointDisabled);
+    s.client
+        .set_entrypoint_enabled(&s.admin, &symbol_short!("buy"), &true);
+
+    // Form bắt buộc được báo trong kết quả
+    s.client
+        .set_purchase_form(&creator, &TokenId(id), &uri(env, "ipfs://form"), &true);
+    assert!(
+        s.client
+            .simulate_buy(&TokenId(id), &outsider, &1_000, &None, &None)
+            .form_required
+    );
+}
+
+// ===========================
+// Cohort fan theo trang (synth-256)
+// ===========================
+
+#[test]
+fn cohort_index_is_paged() {
+    let s = setup();
+    let env = &s.env;
+    let granter = Address::generate(env);
+    let mut total: u128 = 0;
+    let mut first = None;
+    for i in 0..=COHORT_PAGE_SIZE {
+        let fan = Address::generate(env);
+        let points = (i + 1) as u128;
+        // Mỗi lần gọi có ngân sách riêng như trên mạng
+        env.budget().reset_default();
+        s.client.award_fan_points(&granter, &fan, &points, &None);
+        total += points;
+        first.get_or_insert(fan);
+    }
+
+    // Thống kê tính trên cả cohort, không theo trang
+    assert_eq!(s.client.get_cohort_stats(&0), (COHORT_PAGE_SIZE + 1, total));
+    assert_eq!(s.client.get_cohort_members(&0, &0).len(), COHORT_PAGE_SIZE);
+    assert_eq!(s.client.get_cohort_members(&0, &1).len(), 1);
+
+    // Điểm tiêu đi được trừ khỏi cohort của fan, kể cả khi nhận thêm ở tuần sau
+    let first = first.unwrap();
+    s.client.burn_points(&first, &1, &None);
+    // Giữ instance sống qua một tuần ledger
+    env.as_contract(&s.client.address, || {
+        env.storage()
+            .instance()
+            .extend_ttl(LEDGERS_PER_WEEK * 2, LEDGERS_PER_WEEK * 2)
+    });
+    set_ledger(env, LEDGERS_PER_WEEK + 1);
+    s.client.award_fan_points(&granter, &first, &10, &None);
+    assert_eq!(
+        s.client.get_cohort_stats(&0),
+        (COHORT_PAGE_SIZE + 1, total + 9)
+    );
+    assert_eq!(s.client.get_cohort_stats(&1), (0, 0));
+}
+
+// ===========================
+// URI dự phòng do admin ghim (synth-256~2)
+// ===========================
+
+#[test]
+fn backup_uri_set_and_clear() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, fan) = (Address::generate(env), Address::generate(env));
+    let id = mint(&s, &creator, &fan, 0);
+
+    s.client
+        .set_backup_uri(&s.admin, &TokenId(id), &uri(env, "ar://mirror"));
+    assert!(emitted(env, symbol_short!("bkp_set")));
+    let ext = s.client.get_info_ext(&TokenId(id));
+    assert_eq!(ext.backup_uri, Some(uri(env, "ar://mirror")));
+    // URI gốc giữ nguyên
+    assert_eq!(ext.info.uri, uri(env, "ipfs://token"));
+
+    s.client
+        .set_backup_uri(&s.admin, &TokenId(id), &Bytes::new(env));
+    assert!(emitted(env, symbol_short!("bkp_clear")));
+    assert!(s.client.get_backup_uri(&TokenId(id)).is_none());
+}
+
+#[test]
+fn backup_uri_respects_creator_opt_out() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, other, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = mint(&s, &creator, &fan, 0);
+    s.client.set_allow_backup(&creator, &false);
+    assert_eq!(
+        err(s
+            .client
+            .try_set_backup_uri(&s.admin, &TokenId(id), &uri(env, "ar://m"))),
+        Error::NotAuthorized
+    );
+
+    // Token collab: một creator từ chối là đủ chặn
+    let shared = collab(&s, &other, &creator, &fan);
+    assert_eq!(
+        err(s
+            .client
+            .try_set_backup_uri(&s.admin, &TokenId(shared), &uri(env, "ar://m"))),
+        Error::NotAuthorized
+    );
+
+    s.client.set_allow_backup(&creator, &true);
+    s.client
+        .set_backup_uri(&s.admin, &TokenId(id), &uri(env, "ar://m"));
+}
+
+// ===========================
+// Form mua hàng (synth-258~2)
+// ===========================
+
+fn response_hash(env: &Env, byte: u8) -> BytesN<32> {
+    BytesN::from_array(env, &[byte; 32])
+}
+
+#[test]
+fn required_form_blocks_buy_without_response() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, seller) = (Address::generate(env), Address::generate(env));
+    let id = mint(&s, &creator, &seller, 0);
+    s.client
+        .set_purchase_form(&creator, &TokenId(id), &uri(env, "ipfs://form"), &true);
+    let buyer = funded(&s, 1_000);
+
+    assert_eq!(
+        err(s.client.try_buy(&TokenId(id), &buyer, &1_000, &None, &None)),
+        Error::FormResponseRequired
+    );
+    let hash = response_hash(env, 7);
+    s.client
+        .buy(&TokenId(id), &buyer, &1_000, &None, &Some(hash.clone()));
+    let response = s.client.get_form_response(&TokenId(id), &buyer).unwrap();
+    assert_eq!(response.response_hash, hash);
+    assert!(!response.fulfilled);
+
+    s.client.mark_form_fulfilled(&creator, &TokenId(id), &buyer);
+    assert!(
+        s.client
+            .get_form_response(&TokenId(id), &buyer)
+            .unwrap()
+            .fulfilled
+    );
+}
+
+#[test]
+fn optional_form_accepts_either() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, seller) = (Address::generate(env), Address::generate(env));
+    let id = mint(&s, &creator, &seller, 0);
+    s.client
+        .set_purchase_form(&creator, &TokenId(id), &uri(env, "ipfs://form"), &false);
+    assert_eq!(
+        s.client.get_purchase_form(&TokenId(id)).unwrap().form_uri,
+        uri(env, "ipfs://form")
+    );
+
+    let first = funded(&s, 1_000);
+    s.client.buy(&TokenId(id), &first, &1_000, &None, &None);
+    assert!(s.client.get_form_response(&TokenId(id), &first).is_none());
+
+    let second = funded(&s, 1_000);
+    s.client.buy(
+        &TokenId(id),
+        &second,
+        &1_000,
+        &None,
+        &Some(response_hash(env, 1)),
+    );
+    assert!(s.client.get_form_response(&TokenId(id), &second).is_some());
+
+    // Chỉ creator mới đánh dấu đã xử lý
+    assert_eq!(
+        err(s
+            .client
+            .try_mark_form_fulfilled(&first, &TokenId(id), &second)),
+        Error::NotAuthorized
+    );
+    // Token có thật nhưng buyer không gửi form
+    assert_eq!(
+        err(s
+            .client
+            .try_mark_form_fulfilled(&creator, &TokenId(id), &first)),
+        Error::NotActive
+    );
+    assert_eq!(
+        err(s
+            .client
+            .try_mark_form_fulfilled(&creator, &TokenId(id + 1), &second)),
+        Error::TokenNotFound
+    );
+}
+
+// ===========================
+// Đổi điểm lấy token (synth-259~2)
+// ===========================
+
+fn points_for(s: &Setup, points: u128) -> Address {
+    let fan = Address::generate(&s.env);
+    let granter = Address::generate(&s.env);
+    s.client.award_fan_points(&granter, &fan, &points, &None);
+    fan
+}
+
+fn fund(s: &Setup, amount: i128, rate: u128, max_per_fan: u128) {
+    s.token.mint(&s.admin, &amount);
+    s.client
+        .fund_conversion(&s.admin, &s.token.address, &amount, &rate, &max_per_fan);
+}
+
+#[test]
+fn conversion_burns_only_whole_units() {
+    let s = setup();
+    fund(&s, 100, 7, 70);
+    let fan = points_for(&s, 50);
+
+    // 50 điểm / 7 = 7 token, đốt 49, giữ 1
+    assert_eq!(s.client.convert_points(&fan, &50), 7);
+    assert_eq!(s.token.balance(&fan), 7);
+    assert_eq!(s.client.get_fan_points(&fan), 1);
+    assert_eq!(s.client.get_conversion().unwrap().remaining, 93);
+
+    assert_eq!(
+        err(s.client.try_convert_points(&fan, &6)),
+        Error::RoundsToZero
+    );
+}
+
+#[test]
+fn conversion_enforces_per_fan_cap() {
+    let s = setup();
+    fund(&s, 100, 7, 70);
+    let fan = points_for(&s, 100);
+    s.client.convert_points(&fan, &49);
+    // 49 + 28 > 70
+    assert_eq!(
+        err(s.client.try_convert_points(&fan, &28)),
+        Error::CapExceeded
+    );
+    assert_eq!(s.client.convert_points(&fan, &21), 3);
+    assert_eq!(s.client.get_fan_points(&fan), 30);
+}
+
+#[test]
+fn conversion_budget_and_refund() {
+    let s = setup();
+    fund(&s, 5, 1, 100);
+    let fan = points_for(&s, 10);
+    assert_eq!(
+        err(s.client.try_convert_points(&fan, &6)),
+        Error::BudgetExhausted
+    );
+    assert_eq!(s.client.get_fan_points(&fan), 10);
+    s.client.convert_points(&fan, &3);
+
+    assert_eq!(s.client.close_conversion(&s.admin), 2);
+    assert_eq!(s.token.balance(&s.admin), 2);
+    assert_eq!(err(s.client.try_convert_points(&fan, &1)), Error::NotActive);
+}
+
+// ===========================
+// Nhật ký admin (synth-262)
+// ===========================
+
+#[test]
+fn admin_log_records_successful_actions_in_order() {
+    let s = setup();
+    let env = &s.env;
+    let token = s.token.address.clone();
+    s.client.set_dust_threshold(&s.admin, &token, &3);
+    // Thao tác lỗi không được ghi
+    assert_eq!(
+        err(s.client.try_set_seller_points_rate(&s.admin, &10_001)),
+        Error::BpsOutOfRange
+    );
+    s.client.set_snapshot_lock(&s.admin, &true);
+
+    assert_eq!(s.client.admin_log_count(), 4);
+    let log = s.client.get_admin_log(&0, &10);
+    let actions: std::vec::Vec<Symbol> = log.iter().map(|e| e.action).collect();
+    assert_eq!(
+        actions,
+        [
+            symbol_short!("init"),
+            symbol_short!("pay_token"),
+            symbol_short!("dust"),
+            symbol_short!("snap_lock"),
+        ]
+    );
+    let dust = log.get(2).unwrap();
+    assert_eq!(dust.actor, s.admin);
+    assert!(matches!(dust.subject, LogSubject::Address(a) if a == token));
+    assert_eq!(dust.ledger, env.ledger().sequence());
+
+    let page = s.client.get_admin_log(&1, &2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap().action, symbol_short!("pay_token"));
+}
+
+#[test]
+fn admin_log_keeps_only_the_newest_entries() {
+    let s = setup();
+    let env = &s.env;
+    // setup đã ghi 2 mục
+    for i in 0..ADMIN_LOG_CAP {
+        env.budget().reset_default();
+        s.client.set_claim_points(&s.admin, &(i as u128));
+    }
+    assert_eq!(s.client.admin_log_count(), ADMIN_LOG_CAP as u64 + 2);
+
+    env.budget().reset_default();
+    let log = s.client.get_admin_log(&0, &(ADMIN_LOG_CAP + 5));
+    assert_eq!(log.len(), ADMIN_LOG_CAP);
+    // Hai mục của setup đã bị ghi đè; mục cũ nhất còn lại là lần set đầu tiên
+    assert_eq!(log.get(0).unwrap().action, symbol_short!("claim_pts"));
+    assert_eq!(s.client.get_admin_log(&(ADMIN_LOG_CAP - 1), &5).len(), 1);
+    assert!(s.client.get_admin_log(&ADMIN_LOG_CAP, &5).is_empty());
+}
+
+// ===========================
+// URI theo ngôn ngữ (synth-263)
+// ===========================
+
+#[test]
+fn uri_variant_falls_back_to_canonical() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, fan) = (Address::generate(env), Address::generate(env));
+    let id = TokenId(mint(&s, &creator, &fan, 0));
+    s.client
+        .set_uri_variant(&creator, &id, &symbol_short!("vi"), &uri(env, "ipfs://vi"));
+
+    assert_eq!(
+        s.client.get_uri_lang(&id, &symbol_short!("vi")),
+        Some(uri(env, "ipfs://vi"))
+    );
+    assert_eq!(
+        s.client.get_uri_lang(&id, &symbol_short!("en")),
+        Some(uri(env, "ipfs://token"))
+    );
+
+    // URI rỗng xoá bản ngôn ngữ
+    s.client
+        .set_uri_variant(&creator, &id, &symbol_short!("vi"), &Bytes::new(env));
+    assert!(s.client.get_uri_langs(&id).is_empty());
+    assert_eq!(
+        s.client.get_uri_lang(&id, &symbol_short!("vi")),
+        Some(uri(env, "ipfs://token"))
+    );
+}
+
+#[test]
+fn uri_variant_cap_and_freeze() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, fan) = (Address::generate(env), Address::generate(env));
+    let id = TokenId(mint(&s, &creator, &fan, 0));
+    let langs = ["vi", "en", "ja", "ko", "fr", "de", "es", "it", "pt"];
+    for lang in langs.iter().take(MAX_URI_VARIANTS as usize) {
+        s.client
+            .set_uri_variant(&creator, &id, &Symbol::new(env, lang), &uri(env, lang));
+    }
+    assert_eq!(s.client.get_uri_langs(&id).len(), MAX_URI_VARIANTS);
+    let extra = Symbol::new(env, langs[MAX_URI_VARIANTS as usize]);
+    assert_eq!(
+        err(s
+            .client
+            .try_set_uri_variant(&creator, &id, &extra, &uri(env, "x"))),
+        Error::CapExceeded
+    );
+    // Ghi đè bản đã có không tính thêm
+    s.client
+        .set_uri_variant(&creator, &id, &symbol_short!("vi"), &uri(env, "v2"));
+
+    s.client.freeze_metadata(&creator, &id);
+    assert_eq!(
+        err(s
+            .client
+            .try_set_uri_variant(&creator, &id, &symbol_short!("vi"), &uri(env, "v3"))),
+        Error::MetadataFrozen
+    );
+}
+
+// ===========================
+// Nhận miễn phí (synth-265)
+// ===========================
+
+fn claim_tokens(s: &Setup, creator: &Address, count: u32) -> Vec<TokenId> {
+    let mut ids = Vec::new(&s.env);
+    for _ in 0..count {
+        ids.push_back(TokenId(mint(s, creator, creator, 0)));
+    }
+    ids
+}
+
+#[test]
+fn claim_window_boundaries() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let fan = Address::generate(env);
+    let ids = claim_tokens(&s, &creator, 2);
+    set_ledger(env, 10);
+    let claim = s.client.create_claim(&creator, &ids, &1, &20, &30);
+
+    set_ledger(env, 19);
+    assert_eq!(err(s.client.try_claim_free(&claim, &fan)), Error::NotActive);
+    set_ledger(env, 20);
+    s.client.claim_free(&claim, &fan);
+    set_ledger(env, 31);
+    let late = Address::generate(env);
+    assert_eq!(
+        err(s.client.try_claim_free(&claim, &late)),
+        Error::NotActive
+    );
+}
+
+#[test]
+fn claim_cap_exhaustion_and_points() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let (a, b) = (Address::generate(env), Address::generate(env));
+    s.client.set_claim_points(&s.admin, &25);
+    let ids = claim_tokens(&s, &creator, 3);
+    let claim = s.client.create_claim(&creator, &ids, &2, &0, &100);
+
+    // Token trong đợt bị khoá
+    let outsider = Address::generate(env);
+    assert_eq!(
+        err(s
+            .client
+            .try_transfer(&ids.get(2).unwrap(), &creator, &outsider)),
+        Error::TokenLocked
+    );
+
+    let first = s.client.claim_free(&claim, &a);
+    assert_eq!(s.client.get_info(&first).owner, a);
+    assert_eq!(s.client.get_fan_points(&a), 25);
+    s.client.claim_free(&claim, &a);
+    assert_eq!(err(s.client.try_claim_free(&claim, &a)), Error::CapExceeded);
+    s.client.claim_free(&claim, &b);
+    let c = Address::generate(env);
+    assert_eq!(
+        err(s.client.try_claim_free(&claim, &c)),
+        Error::BudgetExhausted
+    );
+    // Token đã nhận không còn bị khoá
+    s.client.transfer(&first, &a, &c);
+}
+
+#[test]
+fn close_claim_unlocks_leftovers() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let fan = Address::generate(env);
+    let ids = claim_tokens(&s, &creator, 3);
+    let claim = s.client.create_claim(&creator, &ids, &1, &0, &10);
+    s.client.claim_free(&claim, &fan);
+
+    assert_eq!(err(s.client.try_close_claim(&claim)), Error::AlreadyActive);
+    set_ledger(env, 11);
+    assert_eq!(s.client.close_claim(&claim), 2);
+    s.client.transfer(&ids.get(1).unwrap(), &creator, &fan);
+    assert!(s.client.get_claim(&claim).is_none());
+}
+
+// ===========================
+// Gộp dust (synth-268~2)
+// ===========================
+
+type SaleEvent = (
+    Address,
+    Address,
+    i128,
+    Option<BytesN<32>>,
+    Vec<(Symbol, i128)>,
+    bool,
+);
+
+// Event "sale" gần nhất
+fn sale_event(env: &Env) -> SaleEvent {
+    let (_, _, data) = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(_, topics, _)| {
+            topics
+                .get(0)
+                .and_then(|t| Symbol::try_from_val(env, &t).ok())
+                .is_some_and(|t| t == symbol_short!("sale"))
+        })
+        .last()
+        .expect("sale event");
+    SaleEvent::try_from_val(env, &data).unwrap()
+}
+
+#[test]
+fn dust_legs_fold_into_largest() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b, seller) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    // Royalty 10% của 100 = 10: A 3, B 7, seller 90
+    let id = s
+        .client
+        .mint_collab(&a, &b, &seller, &1_000, &uri(env, "ipfs://c"), &3_000)
+        .0;
+    s.client.set_dust_threshold(&s.admin, &s.token.address, &8);
+    let buyer = funded(&s, 100);
+    s.client.buy(&TokenId(id), &buyer, &100, &None, &None);
+
+    let dust = sale_event(env).4;
+    assert_eq!(dust.len(), 2);
+    assert_eq!(dust.get(0).unwrap(), (symbol_short!("creator"), 3));
+    assert_eq!(dust.get(1).unwrap(), (Symbol::new(env, "co_creatr"), 7));
+    assert_eq!(s.token.balance(&seller), 100);
+    assert_eq!(s.token.balance(&a) + s.token.balance(&b), 0);
+    assert_eq!(s.token.balance(&buyer), 0);
+}
+
+#[test]
+fn dust_threshold_is_bounded() {
+    let s = setup();
+    let token = s.token.address.clone();
+    assert_eq!(
+        err(s.client.try_set_dust_threshold(&s.admin, &token, &-1)),
+        Error::InvalidPrice
+    );
+    assert_eq!(
+        err(s
+            .client
+            .try_set_dust_threshold(&s.admin, &token, &(MAX_DUST_THRESHOLD + 1))),
+        Error::CapExceeded
+    );
+    s.client
+        .set_dust_threshold(&s.admin, &token, &MAX_DUST_THRESHOLD);
+    s.client.set_dust_threshold(&s.admin, &token, &0);
+}
+
+// ===========================
+// Chuyển cưỡng chế qua hội đồng (synth-272~2)
+// ===========================
+
+// Hội đồng 2/3, timelock 100 giây; trả về các thành viên
+fn council(s: &Setup) -> [Address; 3] {
+    let env = &s.env;
+    let signers = [
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    ];
+    let mut list = Vec::new(env);
+    for signer in signers.iter() {
+        list.push_back(signer.clone());
+    }
+    s.client.set_council(&s.admin, &list, &2, &100);
+    signers
+}
+
+// Thông qua một GovAction với đủ chữ ký và chờ hết timelock
+fn pass(s: &Setup, signers: &[Address; 3], action: GovAction) {
+    let id = s.client.propose_gov_action(&signers[0], &action);
+    s.client.approve_gov_action(&signers[1], &id);
+    advance_time(&s.env, 100);
+    s.client.execute_gov_action(&id);
+}
+
+#[test]
+fn emergency_transfer_needs_council_and_timelock() {
+    let s = setup();
+    let env = &s.env;
+    let signers = council(&s);
+    let (creator, victim, rescue) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = mint(&s, &creator, &victim, 0);
+    let reason = uri(env, "court order 42");
+
+    // Chưa có hạn mức
+    assert_eq!(
+        err(s
+            .client
+            .try_emergency_transfer(&s.admin, &TokenId(id), &rescue, &reason)),
+        Error::CapExceeded
+    );
+    pass(&s, &signers, GovAction::SetMaxEmergencyTransfers(1));
+
+    let proposal = s
+        .client
+        .emergency_transfer(&s.admin, &TokenId(id), &rescue, &reason);
+    assert_eq!(s.client.get_info(&TokenId(id)).owner, victim);
+    s.client.approve_gov_action(&signers[0], &proposal);
+    assert_eq!(
+        err(s.client.try_execute_gov_action(&proposal)),
+        Error::InsufficientApprovals
+    );
+    s.client.approve_gov_action(&signers[2], &proposal);
+    assert_eq!(
+        err(s.client.try_execute_gov_action(&proposal)),
+        Error::TimelockActive
+    );
+    advance_time(env, 100);
+    s.client.execute_gov_action(&proposal);
+
+    assert_eq!(s.client.get_info(&TokenId(id)).owner, rescue);
+    let record = s.client.get_emergency_transfers().get(0).unwrap();
+    assert_eq!(record.from, victim);
+    assert_eq!(record.reason, reason);
+
+    // Hết hạn mức
+    assert_eq!(
+        err(s
+            .client
+            .try_emergency_transfer(&s.admin, &TokenId(id), &victim, &reason)),
+        Error::CapExceeded
+    );
+}
+
+#[test]
+fn emergency_transfer_rejects_locked_and_shared_tokens() {
+    let s = setup();
+    let env = &s.env;
+    let signers = council(&s);
+    pass(&s, &signers, GovAction::SetMaxEmergencyTransfers(5));
+    let (creator, holder, rescue) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let reason = uri(env, "hack");
+
+    // Token đang trong đợt claim
+    let ids = claim_tokens(&s, &creator, 1);
+    s.client.create_claim(&creator, &ids, &1, &0, &100);
+    assert_eq!(
+        err(s
+            .client
+            .try_emergency_transfer(&s.admin, &ids.get(0).unwrap(), &rescue, &reason)),
+        Error::TokenLocked
+    );
+
+    // Token đồng sở hữu
+    let shared = mint(&s, &creator, &holder, 0);
+    let mut holders = Vec::new(env);
+    holders.push_back((holder.clone(), 6_000u32));
+    holders.push_back((rescue.clone(), 4_000u32));
+    s.client
+        .fractionalize_control(&holder, &TokenId(shared), &holders, &None);
+    assert_eq!(
+        err(s
+            .client
+            .try_emergency_transfer(&s.admin, &TokenId(shared), &rescue, &reason)),
+        Error::TokenLocked
+    );
+
+    // Snapshot khoá sau khi đề xuất: lúc thực thi vẫn bị chặn
+    let plain = mint(&s, &creator, &holder, 0);
+    let proposal = s
+        .client
+        .emergency_transfer(&s.admin, &TokenId(plain), &rescue, &reason);
+    s.client.approve_gov_action(&signers[0], &proposal);
+    s.client.approve_gov_action(&signers[1], &proposal);
+    advance_time(env, 100);
+    s.client.set_snapshot_lock(&s.admin, &true);
+    assert_eq!(
+        err(s.client.try_execute_gov_action(&proposal)),
+        Error::TokenLocked
+    );
+}
+
+// ===========================
+// Sao kê theo kỳ (synth-274~2)
+// ===========================
+
+#[test]
+fn statements_isolate_each_period() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let id = TokenId(mint(&s, &creator, &creator, 1_000));
+    let first = funded(&s, 1_000);
+    s.client.buy(&id, &first, &1_000, &None, &None);
+
+    let closed = s.client.close_statement_period(&s.admin);
+    // Đóng lại trong cùng ledger không mở thêm kỳ
+    assert_eq!(s.client.close_statement_period(&s.admin), closed);
+    assert_eq!(closed, 1);
+
+    let second = funded(&s, 500);
+    s.client.buy(&id, &second, &500, &None, &None);
+
+    let p0 = s.client.get_statement(&creator, &0);
+    assert_eq!(
+        (p0.royalty_earned, p0.primary_sales, p0.secondary_sales),
+        (100, 900, 0)
+    );
+    assert_eq!(p0.per_token_breakdown_count, 1);
+    let p1 = s.client.get_statement(&creator, &1);
+    assert_eq!(
+        (p1.royalty_earned, p1.primary_sales, p1.secondary_sales),
+        (50, 0, 500)
+    );
+}
+
+#[test]
+fn collab_statements_split_by_role() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b) = (Address::generate(env), Address::generate(env));
+    // Royalty 10%, A 30%; B giữ token và bán lần đầu
+    let id = TokenId(
+        s.client
+            .mint_collab(&a, &b, &b, &1_000, &uri(env, "ipfs://c"), &3_000)
+            .0,
+    );
+    let first = funded(&s, 1_000);
+    s.client.buy(&id, &first, &1_000, &None, &None);
+
+    let st_a = s.client.get_statement(&a, &0);
+    let st_b = s.client.get_statement(&b, &0);
+    assert_eq!(
+        (
+            st_a.royalty_earned,
+            st_a.primary_sales,
+            st_a.secondary_sales
+        ),
+        (30, 0, 0)
+    );
+    assert_eq!(
+        (
+            st_b.royalty_earned,
+            st_b.primary_sales,
+            st_b.secondary_sales
+        ),
+        (70, 900, 0)
+    );
+
+    // Bán lại: doanh số thứ cấp chỉ ghi cho creator chính
+    let second = funded(&s, 500);
+    s.client.buy(&id, &second, &500, &None, &None);
+    let st_a = s.client.get_statement(&a, &0);
+    let st_b = s.client.get_statement(&b, &0);
+    assert_eq!((st_a.royalty_earned, st_a.secondary_sales), (45, 500));
+    assert_eq!((st_b.royalty_earned, st_b.secondary_sales), (105, 0));
+}
+
+// ===========================
+// Chuyển quyền creator (synth-275)
+// ===========================
+
+#[test]
+fn creatorship_chain_collapses_to_one_hop() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b, c, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let first = mint(&s, &a, &fan, 1_000);
+    let second = mint(&s, &a, &fan, 1_000);
+    s.client.transfer_creatorship(&a, &b);
+    s.client.transfer_creatorship(&b, &c);
+
+    // A→B rồi B→C được ghi lại thành A→C, B→C
+    assert_eq!(s.client.get_creator_redirect(&a), Some(c.clone()));
+    assert_eq!(s.client.get_creator_redirect(&b), Some(c.clone()));
+    assert_eq!(s.client.get_creator_tokens(&c).len(), 2);
+    assert_eq!(s.client.get_creator_tokens(&a).len(), 0);
+
+    // Royalty đi thẳng tới C ngay sau khi chuyển
+    let buyer = funded(&s, 1_000);
+    s.client.buy(&TokenId(first), &buyer, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&c), 100);
+    assert_eq!(s.token.balance(&a), 0);
+
+    // Vòng lặp và chuyển tiếp từ địa chỉ đã chuyển đều bị từ chối
+    assert_eq!(
+        err(s.client.try_transfer_creatorship(&c, &a)),
+        Error::InvalidRedirect
+    );
+    assert_eq!(
+        err(s.client.try_transfer_creatorship(&b, &fan)),
+        Error::InvalidRedirect
+    );
+
+    // Ghi lại Creator theo trang
+    assert_eq!(s.client.materialize_creatorship(&c, &first, &1), 1);
+    assert_eq!(s.client.materialize_creatorship(&c, &0, &10), 1);
+    assert_eq!(s.client.materialize_creatorship(&c, &0, &10), 0);
+    let buyer = funded(&s, 1_000);
+    s.client.buy(&TokenId(second), &buyer, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&c), 200);
+}
+
+#[test]
+fn creatorship_moves_creator_settings() {
+    let s = setup();
+    let env = &s.env;
+    let (old, new) = (Address::generate(env), Address::generate(env));
+    s.client.set_min_listing_fee(&old, &50);
+    s.client.set_exclusivity_period(&old, &100);
+    s.client.set_accept_returns(&old, &true, &7);
+    s.client.set_allow_backup(&old, &false);
+    let mut windows = Vec::new(env);
+    windows.push_back(MintWindow { start: 10, end: 20 });
+    s.client.set_minting_windows(&old, &windows);
+    s.client
+        .schedule_promo(&old, &1_000, &0, &(env.ledger().sequence() + 10));
+
+    s.client.transfer_creatorship(&old, &new);
+    assert_eq!(s.client.get_min_listing_fee(&new), 50);
+    assert_eq!(s.client.get_min_listing_fee(&old), 0);
+    assert_eq!(s.client.accepts_returns(&new), Some(7));
+    assert_eq!(s.client.accepts_returns(&old), None);
+    assert_eq!(s.client.get_minting_windows(&new).len(), 1);
+    assert_eq!(s.client.get_promos(&new).len(), 1);
+    assert_eq!(s.client.get_promos(&old).len(), 0);
+
+    // Đích đã có cấu hình cùng loại thì không tự gộp
+    let (other, taken) = (Address::generate(env), Address::generate(env));
+    s.client.set_min_listing_fee(&other, &10);
+    s.client.set_min_listing_fee(&taken, &20);
+    assert_eq!(
+        err(s.client.try_transfer_creatorship(&other, &taken)),
+        Error::InvalidRedirect
+    );
+    assert_eq!(s.client.get_creator_redirect(&other), None);
+}
+
+#[test]
+fn creatorship_moves_unreleased_royalty_holds() {
+    let s = setup();
+    let env = &s.env;
+    let (old, b, new, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    s.client.set_royalty_hold_period(&s.admin, &100);
+    let id = TokenId(collab(&s, &old, &b, &fan));
+    let buyer = funded(&s, 1_000);
+    s.client.buy(&id, &buyer, &1_000, &None, &None);
+
+    s.client.transfer_creatorship(&old, &new);
+    let pending = s.client.get_pending_royalties(&id);
+    assert_eq!(pending.len(), 2);
+    assert!(pending.iter().all(|p| p.creator != old));
+
+    advance_time(env, 100);
+    assert_eq!(
+        err(s.client.try_claim_pending_royalty(&old, &id)),
+        Error::NotActive
+    );
+    assert_eq!(s.client.claim_pending_royalty(&new, &id), 1);
+    assert_eq!(s.token.balance(&new), 24);
+    // Phần của co-creator không bị đụng tới
+    assert_eq!(s.client.claim_pending_royalty(&b, &id), 1);
+    assert_eq!(s.token.balance(&b), 56);
+    assert_eq!(s.token.balance(&old), 0);
+}
+
+// ===========================
+// Đổi token kèm tiền (synth-276~2)
+// ===========================
+
+#[test]
+fn hybrid_swap_settles_and_clears_record() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, holder_b) = (Address::generate(env), Address::generate(env));
+    let offerer = funded(&s, 1_000);
+    let a = TokenId(mint(&s, &creator, &offerer, 0));
+    let b = TokenId(mint(&s, &creator, &holder_b, 1_000));
+    let expires = env.ledger().timestamp() + 100;
+    let swap_id =
+        s.client
+            .propose_hybrid_swap(&offerer, &a, &1_000, &s.token.address, &b, &expires);
+    assert!(s.client.is_locked(&a));
+
+    s.client.accept_hybrid_swap(&holder_b, &swap_id);
+    assert_eq!(s.client.get_info(&a).owner, holder_b);
+    assert_eq!(s.client.get_info(&b).owner, offerer);
+    assert!(!s.client.is_locked(&a));
+    // Royalty 10% của token B trừ từ tiền
+    assert_eq!(s.token.balance(&creator), 100);
+    assert_eq!(s.token.balance(&holder_b), 900);
+    assert!(s.client.get_hybrid_swap(&swap_id).is_none());
+    assert_eq!(
+        err(s.client.try_accept_hybrid_swap(&holder_b, &swap_id)),
+        Error::NotActive
+    );
+}
+
+#[test]
+fn hybrid_swap_rejects_royalty_bearing_token_a() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, holder_b) = (Address::generate(env), Address::generate(env));
+    let offerer = funded(&s, 1_000);
+    let with_royalty = TokenId(mint(&s, &creator, &offerer, 500));
+    let free = TokenId(mint(&s, &creator, &offerer, 0));
+    let b = TokenId(mint(&s, &Address::generate(env), &holder_b, 0));
+    let expires = env.ledger().timestamp() + 100;
+    assert_eq!(
+        err(s.client.try_propose_hybrid_swap(
+            &offerer,
+            &with_royalty,
+            &100,
+            &s.token.address,
+            &b,
+            &expires
+        )),
+        Error::InvalidRoyalty
+    );
+
+    // Phí tối thiểu đặt sau khi đề nghị vẫn chặn lúc chấp nhận
+    let swap_id =
+        s.client
+            .propose_hybrid_swap(&offerer, &free, &100, &s.token.address, &b, &expires);
+    s.client.set_min_listing_fee(&creator, &10);
+    assert_eq!(
+        err(s.client.try_accept_hybrid_swap(&holder_b, &swap_id)),
+        Error::InvalidRoyalty
+    );
+
+    // Huỷ hoàn tiền, mở khoá và xoá bản ghi
+    s.client.cancel_hybrid_swap(&offerer, &swap_id);
+    assert_eq!(s.token.balance(&offerer), 1_000);
+    assert!(!s.client.is_locked(&free));
+    assert!(s.client.get_hybrid_swap(&swap_id).is_none());
+}
+
+// ===========================
+// NFT thành viên (synth-277)
+// ===========================
+
+fn benefits(discount_bps: u32) -> Benefits {
+    Benefits {
+        discount_bps,
+        early_access_days: 0,
+        max_airdrop_eligible: 0,
+        priority_queue_position: 0,
+    }
+}
+
+#[test]
+fn membership_discount_is_clamped_to_min_fee() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, seller) = (Address::generate(env), Address::generate(env));
+    let member = funded(&s, 2_000);
+    let expires = env.ledger().timestamp() + 1_000;
+    s.client.mint_membership(
+        &s.admin,
+        &member,
+        &1,
+        &benefits(5_000),
+        &expires,
+        &uri(env, "ipfs://member"),
+    );
+    assert_eq!(
+        s.client
+            .get_membership_benefits(&member)
+            .unwrap()
+            .discount_bps,
+        5_000
+    );
+
+    // Giảm 50%: trả 500 cho giá 1_000
+    let id = TokenId(mint(&s, &creator, &seller, 0));
+    s.client.buy(&id, &member, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&seller), 500);
+
+    // Phí tối thiểu 800 giữ giá không xuống dưới 800
+    s.client.set_min_listing_fee(&creator, &800);
+    let id = TokenId(mint(&s, &creator, &seller, 0));
+    s.client.buy(&id, &member, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&member), 700);
+}
+
+#[test]
+fn membership_mint_validation() {
+    let s = setup();
+    let env = &s.env;
+    let member = Address::generate(env);
+    let expires = env.ledger().timestamp() + 1_000;
+    let link = uri(env, "ipfs://member");
+    assert_eq!(
+        err(s.client.try_mint_membership(
+            &s.admin,
+            &member,
+            &1,
+            &benefits(1_000),
+            &expires,
+            &Bytes::new(env)
+        )),
+        Error::EmptyInput
+    );
+    assert_eq!(
+        err(s.client.try_mint_membership(
+            &s.admin,
+            &member,
+            &1,
+            &benefits(10_000),
+            &expires,
+            &link
+        )),
+        Error::BpsOutOfRange
+    );
+    let id = s
+        .client
+        .mint_membership(&s.admin, &member, &1, &benefits(1_000), &expires, &link);
+    assert_eq!(s.client.get_info(&id).uri, link);
+    assert!(s.client.is_locked(&id));
+}
+
+// ===========================
+// Airdrop cho người giữ token theo trang (synth-277~2)
+// ===========================
+
+#[test]
+fn holder_airdrop_pages_over_frozen_snapshot() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, heir, late) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let mut fans: std::vec::Vec<Address> = std::vec::Vec::new();
+    let mut ids: std::vec::Vec<u128> = std::vec::Vec::new();
+    for i in 0..12 {
+        let fan = Address::generate(env);
+        // Token thứ 11 còn nằm trong tay creator nên không nhận điểm
+        let owner = if i == 11 {
+            creator.clone()
+        } else {
+            fan.clone()
+        };
+        ids.push(mint(&s, &creator, &owner, 0));
+        fans.push(fan);
+    }
+    // Token retire vẫn có người giữ nên vẫn được tính
+    s.client.retire_token(&fans[2], &TokenId(ids[2]));
+    let drop = s.client.start_holder_airdrop(&creator, &10);
+    let before: std::vec::Vec<u128> = fans.iter().map(|f| s.client.get_fan_points(f)).collect();
+
+    assert_eq!(s.client.process_airdrop(&drop, &5), 5);
+    // Giữa chừng: token 3 (đã xử lý) và token 7 (chưa) đổi chủ, creator chuyển
+    // quyền sang địa chỉ khác và mint thêm token ngoài snapshot
+    s.client.transfer(&TokenId(ids[3]), &fans[3], &late);
+    s.client.transfer(&TokenId(ids[7]), &fans[7], &late);
+    s.client.transfer_creatorship(&creator, &heir);
+    mint(&s, &heir, &late, 0);
+    assert_eq!(s.client.get_creator_tokens(&creator).len(), 0);
+
+    assert_eq!(s.client.process_airdrop(&drop, &5), 5);
+    assert_eq!(s.client.process_airdrop(&drop, &5), 2);
+    assert!(emitted(env, symbol_short!("drop_done")));
+    assert_eq!(
+        err(s.client.try_process_airdrop(&drop, &5)),
+        Error::NotActive
+    );
+
+    let gained = |i: usize| s.client.get_fan_points(&fans[i]) - before[i];
+    for i in [0, 1, 2, 3, 4, 5, 6, 8, 9, 10] {
+        assert_eq!(gained(i), 10);
+    }
+    // Token 7 được cấp cho chủ lúc xử lý, không cấp hai lần
+    assert_eq!(gained(7), 0);
+    assert_eq!(gained(11), 0);
+    assert_eq!(s.client.get_fan_points(&late), 10);
+}
+
+// ===========================
+// Giá theo số tiền muốn nhận (synth-278~2)
+// ===========================
+
+#[test]
+fn price_for_net_always_reaches_desired_net() {
+    let s = setup();
+    let env = &s.env;
+    let owner = Address::generate(env);
+    let desired = [1i128, 3, 9, 10, 49, 50, 51, 100, 12_345];
+    for threshold in [0i128, 5, 50] {
+        s.client
+            .set_dust_threshold(&s.admin, &s.token.address, &threshold);
+        for bps in [0u32, 1, 333, 2_500, 9_999] {
+            for min_fee in [0i128, 7] {
+                for is_collab in [false, true] {
+                    env.budget().reset_default();
+                    let creator = Address::generate(env);
+                    if min_fee > 0 {
+                        s.client.set_min_listing_fee(&creator, &min_fee);
+                    }
+                    let id = if is_collab {
+                        let other = Address::generate(env);
+                        s.client
+                            .mint_collab(
+                                &creator,
+                                &other,
+                                &owner,
+                                &bps,
+                                &uri(env, "ipfs://c"),
+                                &3_000,
+                            )
+                            .0
+                    } else {
+                        mint(&s, &creator, &owner, bps)
+                    };
+                    let id = TokenId(id);
+                    for x in desired {
+                        env.budget().reset_default();
+                        let price = s.client.price_for_net(&id, &x, &None);
+                        assert!(s.client.net_for_price(&id, &price, &None) >= x);
+                        // Không có dust thì hàm đơn điệu và giá là nhỏ nhất
+                        if threshold == 0 && price > 1 {
+                            assert!(s.client.net_for_price(&id, &(price - 1), &None) < x);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn price_for_net_rejects_impossible_targets() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let id = TokenId(mint(&s, &creator, &creator, 10_000));
+    assert_eq!(
+        err(s.client.try_price_for_net(&id, &100, &None)),
+        Error::InvalidPrice
+    );
+    let id = TokenId(mint(&s, &creator, &creator, 1_000));
+    assert_eq!(
+        err(s.client.try_price_for_net(&id, &0, &None)),
+        Error::InvalidPrice
+    );
+    assert_eq!(
+        err(s.client.try_price_for_net(&id, &i128::MAX, &None)),
+        Error::Overflow
+    );
+}
+
+// ===========================
+// Giới hạn điểm tổng (synth-279)
+// ===========================
+
+#[test]
+fn global_cap_clips_sale_points_but_blocks_awards() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    s.client.set_fan_point_caps(&s.admin, &0, &1_500);
+
+    let first = funded(&s, 1_000);
+    s.client.buy(
+        &TokenId(mint(&s, &creator, &creator, 0)),
+        &first,
+        &1_000,
+        &None,
+        &None,
+    );
+    assert_eq!(s.client.get_fan_points(&first), 1_000);
+
+    // Lần mua thứ hai chỉ còn 500 điểm trống: vẫn hoàn tất, điểm bị cắt
+    let second = funded(&s, 1_000);
+    s.client.buy(
+        &TokenId(mint(&s, &creator, &creator, 0)),
+        &second,
+        &1_000,
+        &None,
+        &None,
+    );
+    assert_eq!(s.client.get_fan_points(&second), 500);
+    assert!(emitted(env, symbol_short!("gpt_cap")));
+
+    // Hết chỗ: mua vẫn được, không có điểm
+    let third = funded(&s, 1_000);
+    let id = TokenId(mint(&s, &creator, &creator, 0));
+    s.client.buy(&id, &third, &1_000, &None, &None);
+    assert_eq!(s.client.get_fan_points(&third), 0);
+    assert_eq!(s.client.get_info(&id).owner, third);
+
+    // Cấp điểm trực tiếp thì báo lỗi
+    assert_eq!(
+        err(s.client.try_award_fan_points(&creator, &third, &1, &None)),
+        Error::GlobalPointCapReached
+    );
+}
+
+// ===========================
+// Trả token về creator (synth-280~2)
+// ===========================
+
+#[test]
+fn return_to_creator_resets_holding_but_keeps_totals() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    s.client.set_exclusivity_period(&creator, &100);
+    let id = TokenId(mint(&s, &creator, &creator, 1_000));
+    let fan = funded(&s, 1_000);
+    s.client.buy(&id, &fan, &1_000, &None, &None);
+    assert_eq!(s.client.get_total_royalty_paid(&id), 100);
+    assert!(s.client.get_exclusivity_end(&id).is_some());
+
+    // Creator chưa bật nhận lại
+    assert_eq!(
+        err(s.client.try_return_to_creator(&fan, &id)),
+        Error::NotAuthorized
+    );
+    s.client.set_accept_returns(&creator, &true, &7);
+    let points = s.client.get_fan_points(&fan);
+    s.client.return_to_creator(&fan, &id);
+    assert!(emitted(env, symbol_short!("returned")));
+
+    assert_eq!(s.client.get_info(&id).owner, creator);
+    assert_eq!(s.client.get_fan_points(&fan), points + 7);
+    assert_eq!(s.client.get_total_royalty_paid(&id), 100);
+    assert!(s.client.get_last_royalty_payment(&id).is_none());
+    assert!(s.client.get_exclusivity_end(&id).is_none());
+
+    // Hạn độc quyền của fan cũ không chặn creator bán lại
+    let next = funded(&s, 500);
+    s.client.buy(&id, &next, &500, &None, &None);
+    assert_eq!(s.client.get_info(&id).owner, next);
+}
+
+// ===========================
+// Đốt điểm và bảng xếp hạng (synth-282~2)
+// ===========================
+
+#[test]
+fn burn_leaderboard_orders_and_trims() {
+    let s = setup();
+    let env = &s.env;
+    let mut fans: std::vec::Vec<Address> = std::vec::Vec::new();
+    for i in 1..=11u128 {
+        let fan = points_for(&s, 1_000);
+        s.client.burn_points(&fan, &(i * 10), &None);
+        fans.push(fan);
+    }
+    let board = s.client.get_burn_leaderboard();
+    assert_eq!(board.len(), 10);
+    assert_eq!(board.get(0).unwrap(), (fans[10].clone(), 110));
+    assert_eq!(board.get(9).unwrap(), (fans[1].clone(), 20));
+
+    // Đốt thêm thì lên hạng; bằng nhau thì người đạt trước đứng trên
+    s.client
+        .burn_points(&fans[0], &100, &Some(symbol_short!("for_art")));
+    assert!(emitted(env, symbol_short!("pt_burn")));
+    let board = s.client.get_burn_leaderboard();
+    assert_eq!(board.get(0).unwrap(), (fans[10].clone(), 110));
+    assert_eq!(board.get(1).unwrap(), (fans[0].clone(), 110));
+    assert_eq!(board.len(), 10);
+    assert!(!board.iter().any(|(a, _)| a == fans[1]));
+
+    assert_eq!(s.client.get_burned_points(&fans[0]), 110);
+    assert_eq!(s.client.get_total_burned_points(), 760);
+    assert_eq!(
+        err(s.client.try_burn_points(&fans[0], &1_000, &None)),
+        Error::InsufficientPoints
+    );
+}
+
+#[test]
+fn burned_points_free_global_cap() {
+    let s = setup();
+    let env = &s.env;
+    s.client.set_fan_point_caps(&s.admin, &0, &1_000);
+    let fan = points_for(&s, 1_000);
+    let granter = Address::generate(env);
+    let other = Address::generate(env);
+    assert_eq!(
+        err(s.client.try_award_fan_points(&granter, &other, &400, &None)),
+        Error::GlobalPointCapReached
+    );
+    s.client.burn_points(&fan, &400, &None);
+    s.client.award_fan_points(&granter, &other, &400, &None);
+    assert_eq!(s.client.get_fan_points(&other), 400);
+    assert_eq!(s.client.get_fan_point_caps().2, 1_000);
+}
+
+// ===========================
+// Operator kế nhiệm khi marketplace đổi địa chỉ (synth-285)
+// ===========================
+
+#[test]
+fn operator_successor_applies_only_in_window() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, owner, old_market, new_market, buyer) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let first = TokenId(mint(&s, &creator, &owner, 0));
+    let second = TokenId(mint(&s, &creator, &owner, 0));
+    set_ledger(env, 100);
+    s.client
+        .set_approval_for_all_until(&owner, &old_market, &true, &1_000);
+
+    // Chỉ admin đang lưu mới đăng ký được
+    assert_eq!(
+        err(s
+            .client
+            .try_register_operator_successor(&old_market, &old_market, &new_market, &200)),
+        Error::NotAuthorized
+    );
+    s.client
+        .register_operator_successor(&s.admin, &old_market, &new_market, &200);
+    assert!(emitted(env, symbol_short!("op_succ")));
+    // Không nối chuỗi
+    assert_eq!(
+        err(s
+            .client
+            .try_register_operator_successor(&s.admin, &new_market, &buyer, &200)),
+        Error::InvalidRedirect
+    );
+
+    set_ledger(env, 200);
+    s.client.transfer_from(&new_market, &first, &owner, &buyer);
+    assert_eq!(s.client.get_info(&first).owner, buyer);
+
+    set_ledger(env, 201);
+    assert_eq!(s.client.get_operator_successors().len(), 0);
+    assert_eq!(
+        err(s
+            .client
+            .try_transfer_from(&new_market, &second, &owner, &buyer)),
+        Error::NotAuthorized
+    );
+    assert_eq!(s.client.prune_operator_successors(), 1);
+    assert_eq!(s.client.prune_operator_successors(), 0);
+}
+
+#[test]
+fn operator_migration_rewrites_approval() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, owner, old_market, new_market, buyer) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = TokenId(mint(&s, &creator, &owner, 0));
+    set_ledger(env, 100);
+    s.client
+        .set_approval_for_all_until(&owner, &old_market, &true, &1_000);
+    s.client
+        .register_operator_successor(&s.admin, &old_market, &new_market, &200);
+    s.client
+        .confirm_operator_migration(&owner, &old_market, &new_market);
+    assert!(emitted(env, symbol_short!("op_migr")));
+    assert!(!s.client.is_approved_for_all(&owner, &old_market));
+
+    // Sau khi mapping hết hạn, approval đã ghi lại vẫn giữ hạn cũ
+    set_ledger(env, 500);
+    assert!(s.client.is_approved_for_all(&owner, &new_market));
+    s.client.transfer_from(&new_market, &id, &owner, &buyer);
+    assert_eq!(s.client.get_info(&id).owner, buyer);
+    assert_eq!(
+        err(s
+            .client
+            .try_confirm_operator_migration(&owner, &old_market, &new_market)),
+        Error::NotActive
+    );
+}
+
+// ===========================
+// Khuyến mãi theo ledger trên mọi đường bán (synth-286)
+// ===========================
+
+#[test]
+fn promo_applies_inside_window_edges() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, buyer) = (Address::generate(env), Address::generate(env));
+    let id = TokenId(mint(&s, &creator, &creator, 0));
+    set_ledger(env, 100);
+    s.client.schedule_promo(&creator, &2_000, &110, &120);
+
+    let quote = |seq: u32| {
+        set_ledger(env, seq);
+        let sim = s.client.simulate_buy(&id, &buyer, &1_000, &None, &None);
+        (sim.effective_price, sim.promo_bps)
+    };
+    assert_eq!(quote(109), (1_000, 0));
+    assert_eq!(quote(110), (800, 2_000));
+    assert_eq!(quote(120), (800, 2_000));
+    assert_eq!(quote(121), (1_000, 0));
+
+    // Giảm xuống dưới phí tối thiểu thì giữ giá gốc và ghi chú lại
+    s.client.set_min_listing_fee(&creator, &900);
+    set_ledger(env, 115);
+    let sim = s.client.simulate_buy(&id, &buyer, &1_000, &None, &None);
+    assert_eq!(sim.effective_price, 1_000);
+    assert!(sim.promo_skipped);
+}
+
+#[test]
+fn promo_applies_to_bundles_and_swaps() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, seller) = (Address::generate(env), Address::generate(env));
+    set_ledger(env, 100);
+    s.client.schedule_promo(&creator, &5_000, &100, &200);
+
+    // Bundle 2 token giá 1_000: mỗi phần 500 giảm còn 250
+    let mut ids = Vec::new(env);
+    ids.push_back(mint(&s, &creator, &seller, 0));
+    ids.push_back(mint(&s, &creator, &seller, 0));
+    let bundle = s
+        .client
+        .propose_cross_bundle(&seller, &ids, &1_000, &s.token.address);
+    s.client.approve_cross_bundle(&creator, &bundle);
+    let buyer = funded(&s, 1_000);
+    s.client.buy_cross_bundle(&buyer, &bundle);
+    assert_eq!(s.token.balance(&buyer), 500);
+    assert_eq!(s.token.balance(&seller), 500);
+
+    // Swap: tiền escrow 1_000, token B chỉ cần 500, phần dư trả lại offerer
+    let offerer = funded(&s, 1_000);
+    let a = TokenId(mint(&s, &Address::generate(env), &offerer, 0));
+    let holder_b = Address::generate(env);
+    let b = TokenId(mint(&s, &creator, &holder_b, 0));
+    let expires = env.ledger().timestamp() + 100;
+    let swap_id =
+        s.client
+            .propose_hybrid_swap(&offerer, &a, &1_000, &s.token.address, &b, &expires);
+    s.client.accept_hybrid_swap(&holder_b, &swap_id);
+    assert_eq!(s.token.balance(&holder_b), 500);
+    assert_eq!(s.token.balance(&offerer), 500);
+}
+
+// ===========================
+// Tặng token kèm điểm (synth-287)
+// ===========================
+
+#[test]
+fn gift_moves_token_and_points_together() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, friend) = (Address::generate(env), Address::generate(env));
+    let fan = points_for(&s, 100);
+    let id = TokenId(mint(&s, &creator, &fan, 0));
+    let memo = uri(env, "happy birthday");
+
+    // Điểm chưa được phép chuyển: token cũng không đổi chủ
+    assert_eq!(
+        err(s
+            .client
+            .try_gift_with_points(&fan, &id, &friend, &10, &memo)),
+        Error::PointsNotTransferable
+    );
+    s.client.set_points_transferable(&s.admin, &true);
+    assert_eq!(
+        err(s
+            .client
+            .try_gift_with_points(&fan, &id, &friend, &101, &memo)),
+        Error::InsufficientPoints
+    );
+    assert_eq!(s.client.get_info(&id).owner, fan);
+
+    s.client.gift_with_points(&fan, &id, &friend, &40, &memo);
+    assert_eq!(s.client.get_info(&id).owner, friend);
+    assert_eq!(s.client.get_fan_points(&fan), 60);
+    assert_eq!(s.client.get_fan_points(&friend), 40);
+    let event = env.events().all().last().unwrap();
+    let data = <(Address, Address, u128, Bytes)>::try_from_val(env, &event.2).unwrap();
+    assert_eq!(data, (fan, friend, 40, memo));
+}
+
+#[test]
+fn gift_rejects_soulbound_and_bad_memo() {
+    let s = setup();
+    let env = &s.env;
+    let friend = Address::generate(env);
+    let fan = points_for(&s, 100);
+    s.client.set_points_transferable(&s.admin, &true);
+    let expires = env.ledger().timestamp() + 1_000;
+    let badge = s.client.mint_membership(
+        &s.admin,
+        &fan,
+        &1,
+        &benefits(0),
+        &expires,
+        &uri(env, "ipfs://member"),
+    );
+    assert_eq!(
+        err(s
+            .client
+            .try_gift_with_points(&fan, &badge, &friend, &0, &Bytes::new(env))),
+        Error::TokenLocked
+    );
+
+    let id = TokenId(mint(&s, &Address::generate(env), &fan, 0));
+    let long_memo = Bytes::from_array(env, &[b'x'; 300]);
+    assert_eq!(
+        err(s
+            .client
+            .try_gift_with_points(&fan, &id, &friend, &0, &long_memo)),
+        Error::CapExceeded
+    );
+
+    // Giới hạn mỗi địa chỉ của người nhận không được cắt mất điểm của người gửi
+    s.client.set_fan_point_caps(&s.admin, &30, &0);
+    assert_eq!(
+        err(s
+            .client
+            .try_gift_with_points(&fan, &id, &friend, &40, &Bytes::new(env))),
+        Error::CapExceeded
+    );
+    assert_eq!(s.client.get_info(&id).owner, fan);
+}
+
+// ===========================
+// Bán token đồng sở hữu (synth-288)
+// ===========================
+
+// Token royalty 0 chia cho (a 60%, b 40%), threshold mặc định 50%
+fn shared_token(s: &Setup, a: &Address, b: &Address) -> TokenId {
+    let owner = Address::generate(&s.env);
+    let id = TokenId(mint(s, &Address::generate(&s.env), &owner, 0));
+    let mut holders = Vec::new(&s.env);
+    holders.push_back((a.clone(), 6_000u32));
+    holders.push_back((b.clone(), 4_000u32));
+    s.client.fractionalize_control(&owner, &id, &holders, &None);
+    id
+}
+
+#[test]
+fn shared_sale_needs_share_above_threshold() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b) = (Address::generate(env), Address::generate(env));
+    let id = shared_token(&s, &a, &b);
+    let buyer = funded(&s, 1_000);
+    let hash = s
+        .client
+        .get_shared_action_hash(&id, &symbol_short!("sell"), &buyer, &1_000);
+
+    // 40% chưa vượt 50%
+    s.client.approve_action(&id, &b, &hash);
+    assert_eq!(
+        err(s.client.try_execute_shared_sale(&id, &buyer, &1_000, &None)),
+        Error::InsufficientApprovals
+    );
+    // Giá khác thì là một hành động khác
+    s.client.approve_action(&id, &a, &hash);
+    assert_eq!(
+        err(s.client.try_execute_shared_sale(&id, &buyer, &999, &None)),
+        Error::InsufficientApprovals
+    );
+
+    s.client.set_seller_points_rate(&s.admin, &1_000);
+    s.client.execute_shared_sale(&id, &buyer, &1_000, &None);
+    assert_eq!(s.client.get_info(&id).owner, buyer);
+    assert!(!s.client.is_locked(&id));
+    assert_eq!(s.token.balance(&a), 600);
+    assert_eq!(s.token.balance(&b), 400);
+    // Điểm seller 10% của giá, chia theo cổ phần
+    assert_eq!(s.client.get_fan_points(&a), 60);
+    assert_eq!(s.client.get_fan_points(&b), 40);
+}
+
+#[test]
+fn shared_sale_exact_threshold_is_not_enough() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b, owner) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = TokenId(mint(&s, &Address::generate(env), &owner, 0));
+    let mut holders = Vec::new(env);
+    holders.push_back((a.clone(), 6_000u32));
+    holders.push_back((b.clone(), 4_000u32));
+    s.client
+        .fractionalize_control(&owner, &id, &holders, &Some(6_000));
+    let buyer = funded(&s, 1_000);
+    let hash = s
+        .client
+        .get_shared_action_hash(&id, &symbol_short!("sell"), &buyer, &1_000);
+    s.client.approve_action(&id, &a, &hash);
+    assert_eq!(
+        err(s.client.try_execute_shared_sale(&id, &buyer, &1_000, &None)),
+        Error::InsufficientApprovals
+    );
+    s.client.approve_action(&id, &b, &hash);
+    s.client.execute_shared_sale(&id, &buyer, &1_000, &None);
+    assert_eq!(s.client.get_info(&id).owner, buyer);
+}
+
+#[test]
+fn shared_sale_respects_snapshot_and_exclusivity() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, a, b) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    s.client.set_exclusivity_period(&creator, &100);
+    let id = TokenId(mint(&s, &creator, &creator, 0));
+    let owner = funded(&s, 500);
+    s.client.buy(&id, &owner, &500, &None, &None);
+    let mut holders = Vec::new(env);
+    holders.push_back((a.clone(), 6_000u32));
+    holders.push_back((b.clone(), 4_000u32));
+    s.client.fractionalize_control(&owner, &id, &holders, &None);
+
+    let buyer = funded(&s, 1_000);
+    let hash = s
+        .client
+        .get_shared_action_hash(&id, &symbol_short!("sell"), &buyer, &1_000);
+    s.client.approve_action(&id, &a, &hash);
+    assert_eq!(
+        err(s.client.try_execute_shared_sale(&id, &buyer, &1_000, &None)),
+        Error::ExclusivityPeriodActive
+    );
+
+    advance_time(env, 100);
+    s.client.set_snapshot_lock(&s.admin, &true);
+    assert_eq!(
+        err(s.client.try_execute_shared_sale(&id, &buyer, &1_000, &None)),
+        Error::TokenLocked
+    );
+    s.client.set_snapshot_lock(&s.admin, &false);
+    s.client.execute_shared_sale(&id, &buyer, &1_000, &None);
+    assert_eq!(s.client.get_info(&id).owner, buyer);
+}
+
+// ===========================
+// Idempotency key khi cấp điểm (synth-291~2)
+// ===========================
+
+// TTL còn lại của key chống replay; key không được nằm trong instance storage
+fn replay_key_ttl<K: IntoVal<Env, Val>>(s: &Setup, key: &K) -> u32 {
+    s.env.as_contract(&s.client.address, || {
+        assert!(!s.env.storage().instance().has(key));
+        assert!(s.env.storage().persistent().has(key));
+        s.env.storage().persistent().get_ttl(key)
+    })
+}
+
+#[test]
+fn grant_keys_live_in_persistent_storage_for_retention() {
+    let s = setup();
+    let env = &s.env;
+    let (granter, fan) = (Address::generate(env), Address::generate(env));
+    s.client.set_grant_key_retention(&s.admin, &50_000);
+    let key = response_hash(env, 4);
+    s.client
+        .award_fan_points(&granter, &fan, &5, &Some(key.clone()));
+    assert!(replay_key_ttl(&s, &FanKey::GrantKey(key)) >= 50_000);
+}
+
+#[test]
+fn grant_key_retry_after_success_is_duplicate() {
+    let s = setup();
+    let env = &s.env;
+    let (granter, fan) = (Address::generate(env), Address::generate(env));
+    let key = response_hash(env, 1);
+    s.client
+        .award_fan_points(&granter, &fan, &50, &Some(key.clone()));
+    assert!(s.client.is_grant_key_used(&key));
+    assert_eq!(
+        err(s
+            .client
+            .try_award_fan_points(&granter, &fan, &50, &Some(key.clone()))),
+        Error::DuplicateGrant
+    );
+    assert_eq!(s.client.get_fan_points(&fan), 50);
+}
+
+#[test]
+fn grant_key_retry_after_failure_applies_once() {
+    let s = setup();
+    let env = &s.env;
+    let (granter, fan) = (Address::generate(env), Address::generate(env));
+    let key = response_hash(env, 2);
+    s.client.set_fan_point_caps(&s.admin, &0, &10);
+    assert_eq!(
+        err(s
+            .client
+            .try_award_fan_points(&granter, &fan, &50, &Some(key.clone()))),
+        Error::GlobalPointCapReached
+    );
+    // Lần lỗi không ghi key nên lần thử lại được áp dụng
+    assert!(!s.client.is_grant_key_used(&key));
+    s.client.set_fan_point_caps(&s.admin, &0, &0);
+    s.client
+        .award_fan_points(&granter, &fan, &50, &Some(key.clone()));
+    assert_eq!(s.client.get_fan_points(&fan), 50);
+}
+
+#[test]
+fn grant_keys_expire_and_prune() {
+    let s = setup();
+    let env = &s.env;
+    let (granter, fan) = (Address::generate(env), Address::generate(env));
+    let key = response_hash(env, 3);
+    set_ledger(env, 100);
+    s.client.set_grant_key_retention(&s.admin, &10);
+    s.client
+        .award_fan_points(&granter, &fan, &5, &Some(key.clone()));
+
+    let mut keys = Vec::new(env);
+    keys.push_back(key.clone());
+    set_ledger(env, 110);
+    assert!(s.client.is_grant_key_used(&key));
+    assert_eq!(s.client.prune_grant_keys(&keys), 0);
+
+    set_ledger(env, 111);
+    assert!(!s.client.is_grant_key_used(&key));
+    assert_eq!(s.client.prune_grant_keys(&keys), 1);
+    s.client.award_fan_points(&granter, &fan, &5, &Some(key));
+    assert_eq!(s.client.get_fan_points(&fan), 10);
+}
+
+// ===========================
+// Owner root cho snapshot off-chain (synth-292)
+// ===========================
+
+// h0 = 32 byte 0; h_i = sha256(h_{i-1} || token_id 16 byte BE || xdr(owner))
+fn expected_root(env: &Env, owners: &[(u128, Address)]) -> BytesN<32> {
+    let mut hash = [0u8; 32];
+    for (id, owner) in owners {
+        let mut data = Bytes::from_array(env, &hash);
+        data.extend_from_array(&id.to_be_bytes());
+        data.append(&owner.clone().to_xdr(env));
+        hash = env.crypto().sha256(&data).to_array();
+    }
+    BytesN::from_array(env, &hash)
+}
+
+#[test]
+fn owner_root_matches_offchain_recomputation() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let mut owners: std::vec::Vec<(u128, Address)> = std::vec::Vec::new();
+    for _ in 0..7 {
+        let owner = Address::generate(env);
+        owners.push((mint(&s, &creator, &owner, 0), owner));
+    }
+    s.client.set_snapshot_lock(&s.admin, &true);
+
+    let (next, _) = s.client.build_owner_root(&None, &3);
+    assert_eq!(next, Some(4));
+    // Không bắt đầu lại khi lần tích luỹ còn dở, cursor cũ cũng bị từ chối
+    assert_eq!(
+        err(s.client.try_build_owner_root(&None, &3)),
+        Error::AlreadyActive
+    );
+    assert_eq!(
+        err(s.client.try_build_owner_root(&Some(1), &3)),
+        Error::NotActive
+    );
+    assert_eq!(err(s.client.try_finalize_owner_root()), Error::NotActive);
+    let (next, _) = s.client.build_owner_root(&next, &3);
+    assert_eq!(next, Some(7));
+    let (next, hash) = s.client.build_owner_root(&next, &3);
+    assert_eq!(next, None);
+    assert_eq!(hash, expected_root(env, &owners));
+
+    let (snapshot_id, root) = s.client.finalize_owner_root();
+    assert_eq!(root, hash);
+    let stored = s.client.get_owner_root(&snapshot_id).unwrap();
+    assert_eq!((stored.root, stored.token_count), (hash, 7));
+    // Finalize mở khoá và cho phép lần chụp mới
+    s.client
+        .transfer(&TokenId(owners[0].0), &owners[0].1, &creator);
+    s.client.build_owner_root(&None, &10);
+}
+
+#[test]
+fn snapshot_lock_blocks_every_owner_change() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, a, b, fan) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let plain = TokenId(mint(&s, &creator, &creator, 0));
+    let ids = claim_tokens(&s, &creator, 1);
+    let claim = s.client.create_claim(&creator, &ids, &1, &0, &1_000);
+    let shared = shared_token(&s, &a, &b);
+    let hash = s
+        .client
+        .get_shared_action_hash(&shared, &symbol_short!("defrac"), &fan, &0);
+    s.client.approve_action(&shared, &a, &hash);
+
+    s.client.set_snapshot_lock(&s.admin, &true);
+    assert_eq!(
+        err(s.client.try_transfer(&plain, &creator, &fan)),
+        Error::TokenLocked
+    );
+    assert_eq!(
+        err(s.client.try_claim_free(&claim, &fan)),
+        Error::TokenLocked
+    );
+    assert_eq!(
+        err(s.client.try_defractionalize(&shared, &fan)),
+        Error::TokenLocked
+    );
+
+    s.client.set_snapshot_lock(&s.admin, &false);
+    s.client.claim_free(&claim, &fan);
+    s.client.defractionalize(&shared, &fan);
+    assert_eq!(s.client.get_info(&shared).owner, fan);
+}
+
+// ===========================
+// Nonce chuyển token có chữ ký theo từng người ký (synth-292~2)
+// ===========================
+
+// Chữ ký trên (token_id || from || to || nonce || deadline || contract_id)
+fn sign_transfer(
+    s: &Setup,
+    key: &SigningKey,
+    id: u128,
+    from: &Address,
+    to: &Address,
+    nonce: u128,
+    deadline: u64,
+) -> Bytes {
+    let env = &s.env;
+    let mut message = Bytes::from_array(env, &id.to_be_bytes());
+    message.append(&from.clone().to_xdr(env));
+    message.append(&to.clone().to_xdr(env));
+    message.extend_from_array(&nonce.to_be_bytes());
+    message.extend_from_array(&deadline.to_be_bytes());
+    message.append(&s.client.address.clone().to_xdr(env));
+    let raw: std::vec::Vec<u8> = message.iter().collect();
+    Bytes::from_slice(env, &key.sign(&raw).to_bytes())
+}
+
+#[test]
+fn transfer_nonces_are_per_signer() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, alice, bob, to) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let (alice_key, alice_pub) = admin_key(env, 11);
+    let (bob_key, bob_pub) = admin_key(env, 12);
+    s.client.register_signing_key(&alice, &alice_pub);
+    s.client.register_signing_key(&bob, &bob_pub);
+    let a1 = mint(&s, &creator, &alice, 0);
+    let a2 = mint(&s, &creator, &alice, 0);
+    let b1 = mint(&s, &creator, &bob, 0);
+    let deadline = env.ledger().timestamp() + 100;
+
+    // Cùng nonce 1 cho hai người ký khác nhau đều hợp lệ
+    let sig = sign_transfer(&s, &alice_key, a1, &alice, &to, 1, deadline);
+    s.client
+        .execute_signed_transfer(&TokenId(a1), &alice, &to, &1, &deadline, &sig);
+    let sig = sign_transfer(&s, &bob_key, b1, &bob, &to, 1, deadline);
+    s.client
+        .execute_signed_transfer(&TokenId(b1), &bob, &to, &1, &deadline, &sig);
+    assert_eq!(s.client.get_info(&TokenId(b1)).owner, to);
+
+    let sig = sign_transfer(&s, &alice_key, a2, &alice, &to, 1, deadline);
+    assert_eq!(
+        err(s
+            .client
+            .try_execute_signed_transfer(&TokenId(a2), &alice, &to, &1, &deadline, &sig)),
+        Error::NonceUsed
+    );
+}
+
+#[test]
+fn transfer_nonce_kept_until_deadline() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, alice, to) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let (key, public) = admin_key(env, 13);
+    s.client.register_signing_key(&alice, &public);
+    let id = mint(&s, &creator, &alice, 0);
+    // 100_000 giây ~ 20_000 ledger
+    let deadline = env.ledger().timestamp() + 100_000;
+    let sig = sign_transfer(&s, &key, id, &alice, &to, 7, deadline);
+    s.client
+        .execute_signed_transfer(&TokenId(id), &alice, &to, &7, &deadline, &sig);
+    let ttl = replay_key_ttl(&s, &DataKey::UsedTransferNonce(alice, 7));
+    assert!(ttl >= 20_000);
+}
+
+// ===========================
+// Voucher tiêu điểm do granter chuyển tiếp (synth-293~2)
+// ===========================
+
+// Chữ ký của fan trên xdr(voucher) || xdr(contract)
+fn sign_voucher(s: &Setup, key: &SigningKey, voucher: &SpendVoucher) -> BytesN<64> {
+    let env = &s.env;
+    let mut message = voucher.clone().to_xdr(env);
+    message.append(&s.client.address.clone().to_xdr(env));
+    let raw: std::vec::Vec<u8> = message.iter().collect();
+    BytesN::from_array(env, &key.sign(&raw).to_bytes())
+}
+
+fn voucher(fan: &Address, points: u128, nonce: u64, expiry_ledger: u32) -> SpendVoucher {
+    SpendVoucher {
+        fan: fan.clone(),
+        points,
+        purpose: symbol_short!("merch"),
+        nonce,
+        expiry_ledger,
+    }
+}
+
+#[test]
+fn relayer_cannot_reuse_voucher() {
+    let s = setup();
+    let env = &s.env;
+    let relayer = Address::generate(env);
+    s.client.set_granter(&s.admin, &relayer, &true);
+    let fan = points_for(&s, 100);
+    let (key, public) = admin_key(env, 21);
+    s.client.register_signing_key(&fan, &public);
+    set_ledger(env, 100);
+
+    let v = voucher(&fan, 30, 1, 200);
+    let sig = sign_voucher(&s, &key, &v);
+    s.client.redeem_signed(&relayer, &v, &sig);
+    assert_eq!(s.client.get_fan_points(&fan), 70);
+    assert!(emitted(env, symbol_short!("pt_spend")));
+    assert_eq!(
+        err(s.client.try_redeem_signed(&relayer, &v, &sig)),
+        Error::NonceUsed
+    );
+    assert_eq!(s.client.get_fan_points(&fan), 70);
+}
+
+#[test]
+fn voucher_nonce_kept_until_expiry() {
+    let s = setup();
+    let env = &s.env;
+    let relayer = Address::generate(env);
+    s.client.set_granter(&s.admin, &relayer, &true);
+    let fan = points_for(&s, 100);
+    let (key, public) = admin_key(env, 22);
+    s.client.register_signing_key(&fan, &public);
+    set_ledger(env, 100);
+
+    let v = voucher(&fan, 10, 3, 30_100);
+    s.client
+        .redeem_signed(&relayer, &v, &sign_voucher(&s, &key, &v));
+    assert!(replay_key_ttl(&s, &FanKey::UsedSpendNonce(fan, 3)) >= 30_000);
+}
+
+#[test]
+fn voucher_failures_are_distinct() {
+    let s = setup();
+    let env = &s.env;
+    let relayer = Address::generate(env);
+    s.client.set_granter(&s.admin, &relayer, &true);
+    let fan = points_for(&s, 100);
+    let (key, public) = admin_key(env, 22);
+    set_ledger(env, 100);
+
+    let v = voucher(&fan, 10, 1, 200);
+    let sig = sign_voucher(&s, &key, &v);
+    assert_eq!(
+        err(s.client.try_redeem_signed(&relayer, &v, &sig)),
+        Error::SigningKeyNotRegistered
+    );
+    s.client.register_signing_key(&fan, &public);
+
+    let stranger = Address::generate(env);
+    assert_eq!(
+        err(s.client.try_redeem_signed(&stranger, &v, &sig)),
+        Error::NotAuthorized
+    );
+
+    let old = voucher(&fan, 10, 2, 99);
+    let old_sig = sign_voucher(&s, &key, &old);
+    assert_eq!(
+        err(s.client.try_redeem_signed(&relayer, &old, &old_sig)),
+        Error::VoucherExpired
+    );
+
+    // Chữ ký không khớp voucher bị host từ chối, không phải lỗi contract
+    let tampered = voucher(&fan, 90, 1, 200);
+    assert!(matches!(
+        s.client.try_redeem_signed(&relayer, &tampered, &sig),
+        Err(Err(_))
+    ));
+    assert_eq!(s.client.get_fan_points(&fan), 100);
+}
+
+// ===========================
+// Miễn royalty một lần (synth-294)
+// ===========================
+
+#[test]
+fn waiver_affects_exactly_one_sale() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, seller) = (Address::generate(env), Address::generate(env));
+    let id = TokenId(mint(&s, &creator, &seller, 1_000));
+    s.client.waive_royalty_once(&creator, &id);
+    assert!(s.client.get_royalty_waiver(&id).is_some());
+
+    // Quote chỉ xem, không dùng mất lần miễn
+    let buyer = funded(&s, 1_000);
+    assert_eq!(
+        s.client
+            .simulate_buy(&id, &buyer, &1_000, &None, &None)
+            .royalty,
+        0
+    );
+    s.client.buy(&id, &buyer, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&seller), 1_000);
+    assert_eq!(s.token.balance(&creator), 0);
+    assert!(sale_event(env).5);
+    assert!(s.client.get_royalty_waiver(&id).is_none());
+
+    let next = funded(&s, 1_000);
+    s.client.buy(&id, &next, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&creator), 100);
+    assert!(!sale_event(env).5);
+}
+
+#[test]
+fn waiver_expires_and_can_be_revoked() {
+    let s = setup();
+    let env = &s.env;
+    let (a, b, seller, stranger) = (
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+        Address::generate(env),
+    );
+    let id = TokenId(collab(&s, &a, &b, &seller));
+    assert_eq!(
+        err(s.client.try_waive_royalty_once(&stranger, &id)),
+        Error::NotAuthorized
+    );
+    // Token collab cần cả hai creator ký
+    s.client.waive_royalty_once(&b, &id);
+    assert!(signed(env, &a) && signed(env, &b));
+    s.client.revoke_royalty_waiver(&a, &id);
+    assert!(s.client.get_royalty_waiver(&id).is_none());
+
+    set_ledger(env, 100);
+    s.client.set_waiver_ttl(&s.admin, &10);
+    s.client.waive_royalty_once(&a, &id);
+    set_ledger(env, 111);
+    assert!(s.client.get_royalty_waiver(&id).is_none());
+    let buyer = funded(&s, 1_000);
+    s.client.buy(&TokenId(id.0), &buyer, &1_000, &None, &None);
+    assert_eq!(s.token.balance(&a) + s.token.balance(&b), 80);
+}
+
+// ===========================
+// Kiểm tra đầu vào (synth-295)
+// ===========================
+
+#[test]
+fn degenerate_inputs_use_dedicated_codes() {
+    let s = setup();
+    let env = &s.env;
+    let this = s.client.address.clone();
+    let pay = s.token.address.clone();
+    let fan = Address::generate(env);
+    let creator = Address::generate(env);
+    let id = mint(&s, &creator, &fan, 0);
+    let other = mint(&s, &creator, &fan, 0);
+    let later = env.ledger().timestamp() + 100;
+    let seq = env.ledger().sequence();
+    let no_ids: Vec<TokenId> = Vec::new(env);
+    let ids = Vec::from_array(env, [TokenId(id)]);
+
+    let cases = std::vec![
+        (
+            "create_claim: không có token",
+            err(s
+                .client
+                .try_create_claim(&creator, &no_ids, &1, &seq, &(seq + 10))),
+            Error::EmptyInput,
+        ),
+        (
+            "create_claim: max_per_wallet 0",
+            err(s
+                .client
+                .try_create_claim(&creator, &ids, &0, &seq, &(seq + 10))),
+            Error::ZeroAmount,
+        ),
+        (
+            "start_holder_airdrop: 0 điểm",
+            err(s.client.try_start_holder_airdrop(&creator, &0)),
+            Error::ZeroAmount,
+        ),
+        (
+            "burn_points: 0 điểm",
+            err(s.client.try_burn_points(&fan, &0, &None)),
+            Error::ZeroAmount,
+        ),
+        (
+            "endorse_fan: tự endorse",
+            err(s.client.try_endorse_fan(&fan, &fan)),
+            Error::SelfAddress,
+        ),
+        (
+            "endorse_fan: endorse contract",
+            err(s.client.try_endorse_fan(&fan, &this)),
+            Error::ContractAddress,
+        ),
+        (
+            "tip_creator_tokens: 0",
+            err(s.client.try_tip_creator_tokens(&fan, &creator, &0, &pay)),
+            Error::ZeroAmount,
+        ),
+        (
+            "tip_creator_tokens: âm",
+            err(s.client.try_tip_creator_tokens(&fan, &creator, &-1, &pay)),
+            Error::InvalidPrice,
+        ),
+        (
+            "tip_creator_tokens: tự tip",
+            err(s.client.try_tip_creator_tokens(&fan, &fan, &10, &pay)),
+            Error::SelfAddress,
+        ),
+        (
+            "tip_creator_tokens: token là contract",
+            err(s.client.try_tip_creator_tokens(&fan, &creator, &10, &this)),
+            Error::ContractAddress,
+        ),
+        (
+            "propose_hybrid_swap: âm",
+            err(s.client.try_propose_hybrid_swap(
+                &fan,
+                &TokenId(id),
+                &-1,
+                &pay,
+                &TokenId(id + 1),
+                &later
+            )),
+            Error::InvalidPrice,
+        ),
+        (
+            "propose_hybrid_swap: token là contract",
+            err(s.client.try_propose_hybrid_swap(
+                &fan,
+                &TokenId(id),
+                &10,
+                &this,
+                &TokenId(id + 1),
+                &later
+            )),
+            Error::ContractAddress,
+        ),
+        (
+            "propose_cross_bundle: không có token",
+            err(s
+                .client
+                .try_propose_cross_bundle(&fan, &Vec::new(env), &10, &pay)),
+            Error::EmptyInput,
+        ),
+        (
+            "propose_cross_bundle: giá 0",
+            err(s
+                .client
+                .try_propose_cross_bundle(&fan, &Vec::from_array(env, [id]), &0, &pay)),
+            Error::ZeroAmount,
+        ),
+        (
+            "propose_cross_bundle: giá âm",
+            err(s
+                .client
+                .try_propose_cross_bundle(&fan, &Vec::from_array(env, [id]), &-1, &pay)),
+            Error::InvalidPrice,
+        ),
+        (
+            "propose_cross_bundle: token là contract",
+            err(s
+                .client
+                .try_propose_cross_bundle(&fan, &Vec::from_array(env, [id]), &10, &this)),
+            Error::ContractAddress,
+        ),
+        (
+            "propose_cross_bundle: token lặp",
+            err(s.client.try_propose_cross_bundle(
+                &fan,
+                &Vec::from_array(env, [id, id]),
+                &10,
+                &pay
+            )),
+            Error::DuplicateToken,
+        ),
+        (
+            "propose_cross_bundle: giá dưới số token",
+            err(s.client.try_propose_cross_bundle(
+                &fan,
+                &Vec::from_array(env, [id, other]),
+                &1,
+                &pay
+            )),
+            Error::RoundsToZero,
+        ),
+        (
+            "fund_conversion: 0",
+            err(s.client.try_fund_conversion(&s.admin, &pay, &0, &1, &1)),
+            Error::ZeroAmount,
+        ),
+        (
+            "fund_conversion: tỷ lệ 0",
+            err(s.client.try_fund_conversion(&s.admin, &pay, &10, &0, &1)),
+            Error::ZeroAmount,
+        ),
+        (
+            "fund_conversion: max_per_fan 0",
+            err(s.client.try_fund_conversion(&s.admin, &pay, &10, &1, &0)),
+            Error::ZeroAmount,
+        ),
+        (
+            "fund_conversion: âm",
+            err(s.client.try_fund_conversion(&s.admin, &pay, &-1, &1, &1)),
+            Error::InvalidPrice,
+        ),
+        (
+            "fund_conversion: token là contract",
+            err(s.client.try_fund_conversion(&s.admin, &this, &10, &1, &1)),
+            Error::ContractAddress,
+        ),
+        (
+            "set_endorse_config: bps quá 10_000",
+            err(s.client.try_set_endorse_config(&s.admin, &0, &10_001)),
+            Error::BpsOutOfRange,
+        ),
+        (
+            "set_dust_threshold: âm",
+            err(s.client.try_set_dust_threshold(&s.admin, &pay, &-1)),
+            Error::InvalidPrice,
+        ),
+        (
+            "set_dust_threshold: token là contract",
+            err(s.client.try_set_dust_threshold(&s.admin, &this, &1)),
+            Error::ContractAddress,
+        ),
+        (
+            "mint_membership: uri rỗng",
+            err(s.client.try_mint_membership(
+                &s.admin,
+                &fan,
+                &1,
+                &benefits(0),
+                &later,
+                &Bytes::new(env)
+            )),
+            Error::EmptyInput,
+        ),
+        (
+            "mint_membership: người nhận là contract",
+            err(s.client.try_mint_membership(
+                &s.admin,
+                &this,
+                &1,
+                &benefits(0),
+                &later,
+                &uri(env, "ipfs://member")
+            )),
+            Error::ContractAddress,
+        ),
+        (
+            "mint_membership: giảm 100%",
+            err(s.client.try_mint_membership(
+                &s.admin,
+                &fan,
+                &1,
+                &benefits(10_000),
+                &later,
+                &uri(env, "ipfs://member")
+            )),
+            Error::BpsOutOfRange,
+        ),
+    ];
+    for (case, got, expected) in cases {
+        assert_eq!(got, expected, "{}", case);
+    }
+}
+
+// ===========================
+// Entrypoint cũ: đếm lượt gọi và tắt (synth-298)
+// ===========================
+
+fn buy_count(s: &Setup) -> (u64, bool) {
+    let counts = s.client.get_call_counts();
+    assert_eq!(counts.len(), 1);
+    let (func, count, enabled) = counts.get(0).unwrap();
+    assert_eq!(func, symbol_short!("buy"));
+    (count, enabled)
+}
+
+#[test]
+fn legacy_buy_calls_are_counted() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let buyer = funded(&s, 2_000);
+    let first = mint(&s, &creator, &creator, 0);
+    let second = mint(&s, &creator, &creator, 0);
+    assert_eq!(buy_count(&s), (0, true));
+
+    s.client.buy(&TokenId(first), &buyer, &1_000, &None, &None);
+    assert_eq!(buy_count(&s), (1, true));
+    // Lượt lỗi bị huỷ cùng giao dịch nên không được đếm
+    assert_eq!(
+        err(s.client.try_buy(&TokenId(second), &buyer, &0, &None, &None)),
+        Error::InvalidPrice
+    );
+    assert_eq!(buy_count(&s), (1, true));
+    // simulate_buy chỉ là view, không tính vào lượt gọi
+    s.client
+        .simulate_buy(&TokenId(second), &buyer, &1_000, &None, &None);
+    s.client.buy(&TokenId(second), &buyer, &1_000, &None, &None);
+    assert_eq!(buy_count(&s), (2, true));
+}
+
+#[test]
+fn disabled_legacy_buy_is_rejected_until_reenabled() {
+    let s = setup();
+    let env = &s.env;
+    let creator = Address::generate(env);
+    let buyer = funded(&s, 1_000);
+    let id = mint(&s, &creator, &creator, 0);
+    let buy = symbol_short!("buy");
+
+    assert_eq!(
+        err(s
+            .client
+            .try_set_entrypoint_enabled(&Address::generate(env), &buy, &false)),
+        Error::NotAuthorized
+    );
+    s.client.set_entrypoint_enabled(&s.admin, &buy, &false);
+    assert_eq!(buy_count(&s), (0, false));
+    assert_eq!(
+        err(s.client.try_buy(&TokenId(id), &buyer, &1_000, &None, &None)),
+        Error::EntrypointDisabled
+    );
+    assert_eq!(
+        err(s
+            .client
+            .try_simulate_buy(&TokenId(id), &buyer, &1_000, &None, &None)),
+        Error::EntrypointDisabled
+    );
+    assert_eq!(buy_count(&s), (0, false));
+
+    s.client.set_entrypoint_enabled(&s.admin, &buy, &true);
+    s.client.buy(&TokenId(id), &buyer, &1_000, &None, &None);
+    assert_eq!(buy_count(&s), (1, true));
+    assert_eq!(s.client.get_info(&TokenId(id)).owner, buyer);
+}
+
+#[test]
+fn core_entrypoints_cannot_be_disabled() {
+    let s = setup();
+    let env = &s.env;
+    let (creator, fan) = (Address::generate(env), Address::generate(env));
+    let id = mint(&s, &creator, &creator, 0);
+
+    for func in [symbol_short!("get_info"), symbol_short!("transfer")] {
+        assert_eq!(
+            err(s.client.try_set_entrypoint_enabled(&s.admin, &func, &false)),
+            Error::UnknownAdminOp
+        );
+    }
+    s.client.transfer(&TokenId(id), &creator, &fan);
+    assert_eq!(s.client.get_info(&TokenId(id)).owner, fan);
+    assert_eq!(buy_count(&s), (0, true));
+}